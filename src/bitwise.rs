@@ -0,0 +1,141 @@
+use std::ops::Range;
+
+use crate::parallel;
+use crate::rule::Rule;
+
+/// Reads 64 bits of a `len` bit string stored in 32 bit blocks starting at bit
+/// `pos`, the bits past the end of the string read as zero.
+fn read_bits(blocks: &[u32], len: usize, pos: usize) -> u64 {
+    let (block, bit) = (pos / 32, pos % 32);
+    let block_at = |i: usize| blocks.get(i).copied().unwrap_or(0) as u64;
+    let mut value = (block_at(block) | block_at(block + 1) << 32) >> bit;
+    if bit != 0 {
+        value |= block_at(block + 2) << (64 - bit);
+    }
+    if len - pos < 64 { value & low_mask(len - pos) } else { value }
+}
+
+/// Reads 64 bits of a `len` bit string starting at bit `pos`, wrapping around to
+/// its first bit as often as needed.
+fn read_wrapped(blocks: &[u32], len: usize, pos: usize) -> u64 {
+    let (mut value, mut filled, mut pos) = (0, 0, pos);
+    while filled < 64 {
+        value |= read_bits(blocks, len, pos) << filled;
+        filled += len - pos;
+        pos = 0;
+    }
+    value
+}
+
+fn low_mask(bits: usize) -> u64 {
+    if bits >= 64 { u64::MAX } else { (1 << bits) - 1 }
+}
+
+/// Gets how many 64 bit words a universe of `len` cells takes.
+pub fn word_count(len: usize) -> usize {
+    len.div_ceil(64)
+}
+
+/// Computes the next generation of a two state rule for a whole row major universe
+/// from the blocks of `old` into the blocks of `next`, 64 cells per word read
+/// from and written to a pair of blocks. Each band of words runs on its own task.
+///
+/// The neighbors of a word are read from the universe shifted by their offset as
+/// one string that wraps at its end, so they wrap like a torus everywhere except
+/// the first and last column, where the west and east neighbors come from the
+/// rows above and below. Those cells must be fixed up by the caller.
+pub fn next_generation(
+    old: &[u32],
+    next: &mut [u32],
+    width: usize,
+    height: usize,
+    rule: &Rule,
+    bands: Vec<Range<usize>>,
+) {
+    let len = width * height;
+    // How far ahead each of the eight neighbors is along the string
+    let shifts: Vec<usize> = (-1isize..=1)
+        .flat_map(|dr| (-1isize..=1).map(move |dc| dr * width as isize + dc))
+        .filter(|shift| *shift != 0)
+        .map(|shift| shift.rem_euclid(len as isize) as usize)
+        .collect();
+    // Which counts bring a cell to life and which keep it alive
+    let births: Vec<usize> = (0..=8).filter(|n| rule.next_state(0, *n) == 1).collect();
+    let survivals: Vec<usize> = (0..=8).filter(|n| rule.next_state(1, *n) == 1).collect();
+
+    let blocks = next.len();
+    let block_bands = bands.into_iter().map(|words| words.start * 2..(words.end * 2).min(blocks)).collect();
+    parallel::fill_bands(next, block_bands, |band, out| {
+        for (k, pair) in out.chunks_mut(2).enumerate() {
+            let start = (band.start / 2 + k) * 64;
+            // A 4 bit counter per cell, one word per bit of the count
+            let mut sum = [0u64; 4];
+            for shift in shifts.iter() {
+                let mut carry = read_wrapped(old, len, (start + shift) % len);
+                for plane in sum.iter_mut() {
+                    let next_carry = *plane & carry;
+                    *plane ^= carry;
+                    carry = next_carry;
+                }
+            }
+            let count_is = |n: usize| {
+                sum.iter().enumerate().fold(u64::MAX, |mask, (bit, plane)| {
+                    if n >> bit & 1 == 1 { mask & plane } else { mask & !plane }
+                })
+            };
+            let alive = read_bits(old, len, start);
+            let born = births.iter().fold(0, |mask, n| mask | count_is(*n));
+            let survived = survivals.iter().fold(0, |mask, n| mask | count_is(*n));
+            let word = ((!alive & born) | (alive & survived)) & low_mask(len - start);
+            pair[0] = word as u32;
+            if let Some(high) = pair.get_mut(1) {
+                *high = (word >> 32) as u32;
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{next_generation, read_wrapped, word_count};
+    use crate::parallel;
+    use crate::random;
+    use crate::rule::Rule;
+
+    #[test]
+    fn reads_wrap_around_the_string() {
+        for len in [1usize, 5, 31, 32, 33, 63, 64, 65, 130, 200] {
+            let bits: Vec<bool> = (0..len).map(|i| (i * 7 + i / 3) % 5 < 2).collect();
+            let mut blocks = vec![0u32; len.div_ceil(32)];
+            for (i, bit) in bits.iter().enumerate() {
+                blocks[i / 32] |= (*bit as u32) << (i % 32);
+            }
+            for pos in [0, 1, 3, 31, 32, 33, 63, 64, 65, len - 1] {
+                if pos >= len {
+                    continue;
+                }
+                let value = read_wrapped(&blocks, len, pos);
+                for i in 0..64 {
+                    assert_eq!(value >> i & 1 == 1, bits[(pos + i) % len]);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn banded_generation_matches_one_band() {
+        // An odd number of blocks, so the last word only has its low block
+        let (width, height) = (71usize, 30);
+        let blocks = (width * height).div_ceil(32);
+        let mut old = vec![0u32; blocks];
+        for idx in 0..width * height {
+            old[idx / 32] |= ((random::unit(7, 0, idx, 0) < 0.4) as u32) << (idx % 32);
+        }
+        let rule = Rule::default();
+        let mut single = vec![0u32; blocks];
+        next_generation(&old, &mut single, width, height, &rule, parallel::split(word_count(width * height), 1));
+        let mut banded = vec![0u32; blocks];
+        next_generation(&old, &mut banded, width, height, &rule, parallel::split(word_count(width * height), 5));
+        assert_eq!(single, banded);
+    }
+}
@@ -0,0 +1,436 @@
+use std::any::Any;
+use std::fmt;
+
+use crate::parallel;
+use crate::random;
+use crate::simulation::Simulation;
+
+const MAX_RADIUS: f32 = 30.0;
+// Values are drawn as states 1 to DRAWN_STATES - 1, state 0 is an empty cell
+const DRAWN_STATES: u8 = 255;
+// Values below this are left out of the live cell list
+const VISIBLE_VALUE: f32 = 1.0 / 256.0;
+
+/// Cells within a kernel: row offset, column offset and weight, the weights summing to 1.
+type Kernel = Vec<(isize, isize, f32)>;
+
+/// A continuous rule: one or more ring kernels average the field around a cell
+/// and a growth function turns those averages into the cell's next value.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ContinuousRule {
+    /// Lenia, e.g. "Lenia R=13 peaks=1 mu=0.15 sigma=0.015 dt=0.1".
+    ///
+    /// The kernel is `peaks.len()` concentric rings of bumps out to `radius`, each
+    /// ring weighted by its peak. The potential grows the cell by `dt` times a
+    /// Gaussian bell around `mu` of width `sigma`, scaled to -1..1.
+    Lenia { radius: f32, peaks: Vec<f32>, mu: f32, sigma: f32, dt: f32 },
+    /// SmoothLife, e.g. "SmoothLife ri=4 ra=12 b1=0.278 b2=0.365 d1=0.267 d2=0.445 an=0.028 am=0.147 dt=1".
+    ///
+    /// A disk of radius `ri` gives the inner filling and the ring out to `ra` the
+    /// outer filling. Sigmoids of width `an` and `am` blend between birth `b1..b2`
+    /// for empty and survival `d1..d2` for full cells. A `dt` below 1 moves the
+    /// cell towards that target instead of replacing it.
+    SmoothLife { inner: f32, outer: f32, b1: f32, b2: f32, d1: f32, d2: f32, alpha_n: f32, alpha_m: f32, dt: f32 },
+}
+
+impl ContinuousRule {
+
+    /// Parses a rule name followed by space separated `key=value` settings, every
+    /// setting left out keeps the value of the examples on the variants.
+    pub fn parse(rule: &str) -> Result<ContinuousRule, String> {
+        let mut words = rule.split_whitespace();
+        let kind = words.next().unwrap_or("").to_ascii_lowercase();
+        let mut rule = match kind.as_str() {
+            "lenia" => ContinuousRule::Lenia { radius: 13.0, peaks: vec![1.0], mu: 0.15, sigma: 0.015, dt: 0.1 },
+            "smoothlife" => ContinuousRule::SmoothLife {
+                inner: 4.0, outer: 12.0, b1: 0.278, b2: 0.365, d1: 0.267, d2: 0.445, alpha_n: 0.028, alpha_m: 0.147, dt: 1.0,
+            },
+            _ => return Err(format!("'{}' should start with Lenia or SmoothLife", rule)),
+        };
+        for word in words {
+            let (key, value) = word.split_once('=').ok_or_else(|| format!("'{}' should be key=value", word))?;
+            let number = || value.parse::<f32>().map_err(|_| format!("'{}' is not a number", word));
+            match (&mut rule, key) {
+                (ContinuousRule::Lenia { peaks, .. }, "peaks") => {
+                    *peaks = value.split(',')
+                        .map(|peak| peak.parse::<f32>().map_err(|_| format!("'{}' is not a list of peaks", word)))
+                        .collect::<Result<Vec<f32>, String>>()?;
+                }
+                (ContinuousRule::Lenia { radius, .. }, "R") => *radius = number()?,
+                (ContinuousRule::Lenia { mu, .. }, "mu") => *mu = number()?,
+                (ContinuousRule::Lenia { sigma, .. }, "sigma") => *sigma = number()?,
+                (ContinuousRule::Lenia { dt, .. }, "dt") => *dt = number()?,
+                (ContinuousRule::SmoothLife { inner, .. }, "ri") => *inner = number()?,
+                (ContinuousRule::SmoothLife { outer, .. }, "ra") => *outer = number()?,
+                (ContinuousRule::SmoothLife { b1, .. }, "b1") => *b1 = number()?,
+                (ContinuousRule::SmoothLife { b2, .. }, "b2") => *b2 = number()?,
+                (ContinuousRule::SmoothLife { d1, .. }, "d1") => *d1 = number()?,
+                (ContinuousRule::SmoothLife { d2, .. }, "d2") => *d2 = number()?,
+                (ContinuousRule::SmoothLife { alpha_n, .. }, "an") => *alpha_n = number()?,
+                (ContinuousRule::SmoothLife { alpha_m, .. }, "am") => *alpha_m = number()?,
+                (ContinuousRule::SmoothLife { dt, .. }, "dt") => *dt = number()?,
+                _ => return Err(format!("'{}' is not a setting of this rule", key)),
+            }
+        }
+        let (inner, outer, dt, widths_positive) = match &rule {
+            ContinuousRule::Lenia { radius, peaks, sigma, dt, .. } => (0.0, *radius, *dt, *sigma > 0.0 && !peaks.is_empty()),
+            ContinuousRule::SmoothLife { inner, outer, alpha_n, alpha_m, dt, .. } => (*inner, *outer, *dt, *alpha_n > 0.0 && *alpha_m > 0.0),
+        };
+        if !(1.0..=MAX_RADIUS).contains(&outer) || inner < 0.0 || inner >= outer {
+            return Err(format!("the kernel radius should be between 1 and {}", MAX_RADIUS));
+        }
+        if !(dt > 0.0 && dt <= 1.0 && widths_positive) {
+            return Err(String::from("dt should be in 0..1 and the growth widths above 0"));
+        }
+        Ok(rule)
+    }
+
+    /// Gets the kernels whose averages `next_value` takes, in the same order.
+    fn kernels(&self) -> Vec<Kernel> {
+        match self {
+            ContinuousRule::Lenia { radius, peaks, .. } => {
+                let kernel = ContinuousRule::ring_kernel(*radius, |distance| {
+                    let rings = peaks.len() as f32;
+                    let position = distance / radius * rings;
+                    let (ring, within) = (position.floor() as usize, position.fract());
+                    match peaks.get(ring) {
+                        // A smooth bump across the ring, zero at both of its edges
+                        Some(peak) if within > 0.0 => peak * (4.0 - 1.0 / (within * (1.0 - within))).exp(),
+                        _ => 0.0,
+                    }
+                });
+                vec![kernel]
+            }
+            ContinuousRule::SmoothLife { inner, outer, .. } => vec![
+                ContinuousRule::ring_kernel(*outer, |distance| (distance < *inner) as u8 as f32),
+                ContinuousRule::ring_kernel(*outer, |distance| (distance >= *inner) as u8 as f32),
+            ],
+        }
+    }
+
+    /// Builds a kernel out to `radius` from a weight for each distance, normalized to sum to 1.
+    fn ring_kernel(radius: f32, weight: impl Fn(f32) -> f32) -> Kernel {
+        let reach = radius.ceil() as isize;
+        let mut kernel = Vec::new();
+        for dr in -reach..=reach {
+            for dc in -reach..=reach {
+                let distance = ((dr * dr + dc * dc) as f32).sqrt();
+                let w = if distance <= radius { weight(distance) } else { 0.0 };
+                if w > 0.0 {
+                    kernel.push((dr, dc, w));
+                }
+            }
+        }
+        let total: f32 = kernel.iter().map(|(_, _, w)| w).sum();
+        kernel.iter_mut().for_each(|(_, _, w)| *w /= total);
+        kernel
+    }
+
+    /// Gets the next value of a cell from its value and the average under each kernel.
+    fn next_value(&self, value: f32, averages: &[f32]) -> f32 {
+        let sigmoid = |x: f32, a: f32, alpha: f32| 1.0 / (1.0 + (-(x - a) * 4.0 / alpha).exp());
+        match self {
+            ContinuousRule::Lenia { mu, sigma, dt, .. } => {
+                let growth = 2.0 * (-(averages[0] - mu).powi(2) / (2.0 * sigma * sigma)).exp() - 1.0;
+                (value + dt * growth).clamp(0.0, 1.0)
+            }
+            ContinuousRule::SmoothLife { b1, b2, d1, d2, alpha_n, alpha_m, dt, .. } => {
+                let (m, n) = (averages[0], averages[1]);
+                // How alive the cell counts as decides between the birth and the survival interval
+                let aliveness = sigmoid(m, 0.5, *alpha_m);
+                let low = b1 + (d1 - b1) * aliveness;
+                let high = b2 + (d2 - b2) * aliveness;
+                let target = sigmoid(n, low, *alpha_n) * (1.0 - sigmoid(n, high, *alpha_n));
+                if *dt >= 1.0 {
+                    target
+                } else {
+                    (value + dt * (2.0 * target - 1.0)).clamp(0.0, 1.0)
+                }
+            }
+        }
+    }
+}
+
+impl fmt::Display for ContinuousRule {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ContinuousRule::Lenia { radius, peaks, mu, sigma, dt } => {
+                let peaks: Vec<String> = peaks.iter().map(|peak| peak.to_string()).collect();
+                write!(f, "Lenia R={} peaks={} mu={} sigma={} dt={}", radius, peaks.join(","), mu, sigma, dt)
+            }
+            ContinuousRule::SmoothLife { inner, outer, b1, b2, d1, d2, alpha_n, alpha_m, dt } => write!(
+                f, "SmoothLife ri={} ra={} b1={} b2={} d1={} d2={} an={} am={} dt={}",
+                inner, outer, b1, b2, d1, d2, alpha_n, alpha_m, dt
+            ),
+        }
+    }
+}
+
+/// How values from 0 to 1 are colored.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Colormap {
+    Grayscale,
+    /// Dark blue through green to yellow.
+    Viridis,
+}
+
+impl Colormap {
+
+    /// Parses the name of a colormap as used by the settings bar, e.g. "gray".
+    pub fn parse(name: &str) -> Result<Colormap, String> {
+        match name.trim().to_ascii_lowercase().as_str() {
+            "gray" => Ok(Colormap::Grayscale),
+            "viridis" => Ok(Colormap::Viridis),
+            _ => Err(format!("'{}' is not a colormap", name)),
+        }
+    }
+
+    fn color(&self, value: f32) -> [f32; 3] {
+        match self {
+            Colormap::Grayscale => [value; 3],
+            Colormap::Viridis => {
+                const STOPS: [[f32; 3]; 5] = [
+                    [0.267, 0.005, 0.329],
+                    [0.229, 0.322, 0.546],
+                    [0.128, 0.567, 0.551],
+                    [0.369, 0.789, 0.383],
+                    [0.993, 0.906, 0.144],
+                ];
+                let position = value.clamp(0.0, 1.0) * (STOPS.len() - 1) as f32;
+                let i = (position as usize).min(STOPS.len() - 2);
+                let t = position - i as f32;
+                [0, 1, 2].map(|c| STOPS[i][c] + (STOPS[i + 1][c] - STOPS[i][c]) * t)
+            }
+        }
+    }
+}
+
+/// A wrapping field of values between 0 and 1 run by a continuous rule.
+///
+/// Values are drawn as states through a colormap, so the renderer's view, pan
+/// and zoom work as they do for every other engine.
+pub struct Continuous {
+    width: usize,
+    height: usize,
+    generation: u64,
+    rule: ContinuousRule,
+    kernels: Vec<Kernel>,
+    colormap: Colormap,
+    cells: Vec<f32>,
+    live_cells: Vec<(f32,f32,f32)>
+}
+
+impl Continuous {
+
+    /// Creates a universe running Lenia from a square of noise in the middle.
+    pub fn new(width: usize, height: usize) -> Continuous {
+        let rule = ContinuousRule::parse("Lenia").unwrap();
+        let mut continuous = Continuous {
+            width,
+            height,
+            generation: 0,
+            kernels: rule.kernels(),
+            rule,
+            colormap: Colormap::Viridis,
+            cells: Vec::new(),
+            live_cells: Vec::new()
+        };
+        continuous.reset_cells();
+        continuous
+    }
+
+    pub fn set_colormap(&mut self, colormap: Colormap) {
+        self.colormap = colormap;
+    }
+
+    /// Fills a square twice the kernel radius across in the middle of an empty field with noise.
+    fn reset_cells(&mut self) {
+        self.cells = vec![0.0; self.width * self.height];
+        let side = (2 * self.kernels[0].iter().map(|(dr, _, _)| *dr).max().unwrap_or(1) as usize)
+            .min(self.width)
+            .min(self.height);
+        let (top, left) = ((self.height - side) / 2, (self.width - side) / 2);
+        for row in top..top + side {
+            for col in left..left + side {
+                let idx = row * self.width + col;
+                self.cells[idx] = random::unit(2024, 0, idx, 0) as f32;
+            }
+        }
+        self.refresh_live_cell_list();
+    }
+
+    /// Gets the next values of a band of rows, reading only the current field.
+    fn next_band_values(&self, rows: std::ops::Range<usize>) -> Vec<f32> {
+        let (width, height) = (self.width as isize, self.height as isize);
+        let mut values = Vec::with_capacity(rows.len() * self.width);
+        let mut averages = vec![0.0; self.kernels.len()];
+        for row in rows {
+            for col in 0..self.width {
+                for (average, kernel) in averages.iter_mut().zip(self.kernels.iter()) {
+                    *average = kernel.iter().map(|(dr, dc, w)| {
+                        let r = (row as isize + dr).rem_euclid(height);
+                        let c = (col as isize + dc).rem_euclid(width);
+                        w * self.cells[(r * width + c) as usize]
+                    }).sum();
+                }
+                values.push(self.rule.next_value(self.cells[row * self.width + col], &averages));
+            }
+        }
+        values
+    }
+
+    /// Lists every visible cell with its value quantized into the drawn states.
+    fn refresh_live_cell_list(&mut self) {
+        self.live_cells.clear();
+        for (idx, value) in self.cells.iter().enumerate() {
+            if *value >= VISIBLE_VALUE {
+                let (row, col) = (idx / self.width, idx % self.width);
+                let state = 1.0 + (value * (DRAWN_STATES - 2) as f32).round();
+                self.live_cells.push((row as f32, col as f32, state));
+            }
+        }
+    }
+}
+
+impl Simulation for Continuous {
+    fn width(&self) -> usize {
+        self.width
+    }
+
+    fn height(&self) -> usize {
+        self.height
+    }
+
+    fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    fn tick(&mut self) {
+        self.generation += 1;
+        let bands = parallel::bands(self.height, parallel::MIN_BAND_CELLS / self.width);
+        let continuous = &*self;
+        let values = parallel::map_bands(bands, |rows| continuous.next_band_values(rows));
+        self.cells = values.concat();
+        self.refresh_live_cell_list();
+    }
+
+    fn rule(&self) -> String {
+        self.rule.to_string()
+    }
+
+    fn set_rule(&mut self, rule: &str) -> Result<(), String> {
+        self.rule = ContinuousRule::parse(rule)?;
+        self.kernels = self.rule.kernels();
+        Ok(())
+    }
+
+    fn state_count(&self) -> u8 {
+        DRAWN_STATES
+    }
+
+    /// State 0 is empty and states 1 and up run through the colormap from 0 to 1.
+    fn palette(&self) -> Vec<[f32; 3]> {
+        (0..DRAWN_STATES)
+            .map(|state| self.colormap.color(state.saturating_sub(1) as f32 / (DRAWN_STATES - 2) as f32))
+            .collect()
+    }
+
+    fn get_live_cells(&self) -> &[(f32,f32,f32)] {
+        &self.live_cells
+    }
+
+    fn toggle_cell(&mut self, row: usize, col: usize) {
+        self.toggle_cells(&[(row, col)]);
+    }
+
+    /// Cells at least half full are emptied, others are filled.
+    fn toggle_cells(&mut self, cells: &[(usize, usize)]) {
+        for (row, col) in cells {
+            let value = &mut self.cells[row * self.width + col];
+            *value = if *value >= 0.5 { 0.0 } else { 1.0 };
+        }
+        self.refresh_live_cell_list();
+    }
+
+    /// The given cells are set to 1 and every other cell to 0.
+    fn set_cells(&mut self, cells: &[(usize, usize)]) {
+        self.cells.iter_mut().for_each(|value| *value = 0.0);
+        for (row, col) in cells {
+            self.cells[row * self.width + col] = 1.0;
+        }
+        self.refresh_live_cell_list();
+    }
+
+    fn set_size(&mut self, width: Option<usize>, height: Option<usize>) {
+        if let Some(w) = width {
+            self.width = w;
+        }
+        if let Some(h) = height {
+            self.height = h;
+        }
+        if width.is_some() || height.is_some() {
+            self.generation = 0;
+            self.reset_cells();
+        }
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Continuous, ContinuousRule};
+    use crate::simulation::Simulation;
+
+    #[test]
+    fn parses_rules() {
+        let lenia = ContinuousRule::parse("lenia R=10 peaks=1,0.5 mu=0.2").unwrap();
+        assert_eq!(lenia.to_string(), "Lenia R=10 peaks=1,0.5 mu=0.2 sigma=0.015 dt=0.1");
+        assert_eq!(ContinuousRule::parse(&lenia.to_string()).unwrap(), lenia);
+        let smooth = ContinuousRule::parse("SmoothLife dt=0.5").unwrap();
+        assert_eq!(ContinuousRule::parse(&smooth.to_string()).unwrap(), smooth);
+        assert!(ContinuousRule::parse("Lenia R=40").is_err());
+        assert!(ContinuousRule::parse("Lenia ri=4").is_err());
+        assert!(ContinuousRule::parse("SmoothLife ri=12 ra=4").is_err());
+        assert!(ContinuousRule::parse("B3/S23").is_err());
+    }
+
+    #[test]
+    fn kernels_are_normalized_rings() {
+        for rule in ["Lenia peaks=1,0.3,0.7", "SmoothLife"] {
+            for kernel in ContinuousRule::parse(rule).unwrap().kernels() {
+                let total: f32 = kernel.iter().map(|(_, _, w)| w).sum();
+                assert!((total - 1.0).abs() < 1e-4, "{}", rule);
+            }
+        }
+        // The Lenia bump is zero at the center and at the edge of the ring
+        let kernel = &ContinuousRule::parse("Lenia").unwrap().kernels()[0];
+        assert!(kernel.iter().all(|(dr, dc, _)| (*dr, *dc) != (0, 0)));
+        assert!(kernel.iter().all(|(dr, dc, _)| dr * dr + dc * dc < 13 * 13));
+    }
+
+    #[test]
+    fn lenia_grows_at_mu_and_decays_away_from_it() {
+        let lenia = ContinuousRule::parse("Lenia").unwrap();
+        assert!((lenia.next_value(0.5, &[0.15]) - 0.6).abs() < 1e-6);
+        assert!((lenia.next_value(0.5, &[0.5]) - 0.4).abs() < 1e-6);
+        assert_eq!(lenia.next_value(0.0, &[0.0]), 0.0);
+    }
+
+    #[test]
+    fn empty_field_stays_empty_and_uniform_field_stays_uniform() {
+        for rule in ["Lenia", "SmoothLife"] {
+            let mut continuous = Continuous::new(40, 30);
+            continuous.set_rule(rule).unwrap();
+            continuous.set_cells(&[]);
+            continuous.tick();
+            assert!(continuous.get_live_cells().is_empty(), "{}", rule);
+            continuous.cells.iter_mut().for_each(|value| *value = 0.3);
+            continuous.tick();
+            let first = continuous.cells[0];
+            assert!(continuous.cells.iter().all(|value| (value - first).abs() < 1e-5), "{}", rule);
+        }
+    }
+}
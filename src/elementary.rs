@@ -0,0 +1,298 @@
+use std::any::Any;
+use std::collections::VecDeque;
+use std::fmt;
+
+use crate::simulation::Simulation;
+
+const MAX_STATES: u8 = 10;
+const MAX_RANGE: usize = 5;
+
+/// A rule for a row of cells that looks at the cells within `range` on each side.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Rule1D {
+    /// A Wolfram elementary rule such as "W30": bit `4 * left + 2 * center + right`
+    /// of the number is the next state of the center cell.
+    Elementary(u8),
+    /// A totalistic rule such as "T1599,K3,R1": digit `n` in base `states` of the code
+    /// is the next state of a cell whose neighborhood, itself included, sums to `n`.
+    Totalistic { code: u64, states: u8, range: usize },
+}
+
+impl Rule1D {
+
+    /// Parses "W30", a bare "30", or a totalistic "T1599,K3,R1" where K and R default to 2 and 1.
+    pub fn parse(rule: &str) -> Result<Rule1D, String> {
+        let rule = rule.trim();
+        let elementary = rule.strip_prefix(['W', 'w']).unwrap_or(rule);
+        if let Ok(number) = elementary.parse::<u8>() {
+            return Ok(Rule1D::Elementary(number));
+        }
+        let mut code = None;
+        let mut states = 2;
+        let mut range = 1;
+        for part in rule.split(',').map(|p| p.trim()) {
+            let mut chars = part.chars();
+            let kind = chars.next().map(|c| c.to_ascii_uppercase());
+            let value = chars.as_str();
+            match kind {
+                Some('T') => code = Some(value.parse::<u64>().map_err(|_| format!("'{}' is not a totalistic code", part))?),
+                Some('K') => match value.parse::<u8>() {
+                    Ok(k) if (2..=MAX_STATES).contains(&k) => states = k,
+                    _ => return Err(format!("'{}' is not a state count between 2 and {}", part, MAX_STATES)),
+                },
+                Some('R') => match value.parse::<usize>() {
+                    Ok(r) if (1..=MAX_RANGE).contains(&r) => range = r,
+                    _ => return Err(format!("'{}' is not a range between 1 and {}", part, MAX_RANGE)),
+                },
+                _ => return Err(format!("'{}' is not an elementary rule such as W30 or a totalistic rule such as T1599,K3,R1", rule)),
+            }
+        }
+        let code = code.ok_or_else(|| format!("rule '{}' needs a totalistic code such as T1599", rule))?;
+        // The code has one digit for every sum from 0 to (2r + 1)(k - 1)
+        let digits = (2 * range + 1) as u32 * (states as u32 - 1) + 1;
+        match (states as u64).checked_pow(digits) {
+            Some(limit) if code >= limit => Err(format!("code {} has more than {} digits in base {}", code, digits, states)),
+            _ => Ok(Rule1D::Totalistic { code, states, range }),
+        }
+    }
+
+    pub fn states(&self) -> u8 {
+        match self {
+            Rule1D::Elementary(_) => 2,
+            Rule1D::Totalistic { states, .. } => *states,
+        }
+    }
+
+    pub fn range(&self) -> usize {
+        match self {
+            Rule1D::Elementary(_) => 1,
+            Rule1D::Totalistic { range, .. } => *range,
+        }
+    }
+
+    /// Gets the next state of the middle cell of a neighborhood of `2 * range + 1` cells.
+    pub fn next_state(&self, neighborhood: &[u8]) -> u8 {
+        match self {
+            Rule1D::Elementary(number) => {
+                let pattern = neighborhood.iter().fold(0, |pattern, state| pattern << 1 | *state);
+                number >> pattern & 1
+            }
+            Rule1D::Totalistic { code, states, .. } => {
+                let sum: u32 = neighborhood.iter().map(|state| *state as u32).sum();
+                let digit = (*states as u64).checked_pow(sum).map_or(0, |place| code / place);
+                (digit % *states as u64) as u8
+            }
+        }
+    }
+}
+
+impl fmt::Display for Rule1D {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Rule1D::Elementary(number) => write!(f, "W{}", number),
+            Rule1D::Totalistic { code, states, range } => write!(f, "T{},K{},R{}", code, states, range),
+        }
+    }
+}
+
+/// A one dimensional automaton drawn as a space-time diagram.
+///
+/// Every generation is one row of `width` cells wrapping at the ends. The
+/// universe shows the last `height` generations from top to bottom, so once the
+/// diagram fills the universe it scrolls up by a row every tick.
+pub struct Elementary {
+    width: usize,
+    height: usize,
+    generation: u64,
+    rule: Rule1D,
+    history: VecDeque<Vec<u8>>,
+    live_cells: Vec<(f32,f32,f32)>
+}
+
+impl Elementary {
+
+    /// Creates a universe running rule 30 from a single live cell in the middle of the row.
+    pub fn new(width: usize, height: usize) -> Elementary {
+        let mut elementary = Elementary {
+            width,
+            height,
+            generation: 0,
+            rule: Rule1D::Elementary(30),
+            history: VecDeque::new(),
+            live_cells: Vec::new()
+        };
+        elementary.set_cells(&[(0, width / 2)]);
+        elementary
+    }
+
+    fn current_row(&mut self) -> &mut Vec<u8> {
+        self.history.back_mut().unwrap()
+    }
+
+    fn refresh_live_cell_list(&mut self) {
+        self.live_cells.clear();
+        for (row, states) in self.history.iter().enumerate() {
+            for (col, state) in states.iter().enumerate() {
+                if *state != 0 {
+                    self.live_cells.push((row as f32, col as f32, *state as f32));
+                }
+            }
+        }
+    }
+}
+
+impl Simulation for Elementary {
+    fn width(&self) -> usize {
+        self.width
+    }
+
+    fn height(&self) -> usize {
+        self.height
+    }
+
+    fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    fn tick(&mut self) {
+        self.generation += 1;
+        let row = self.history.back().unwrap();
+        let r = self.rule.range() as isize;
+        let width = self.width as isize;
+        let mut neighborhood = vec![0; 2 * r as usize + 1];
+        let next: Vec<u8> = (0..width).map(|col| {
+            for (state, offset) in neighborhood.iter_mut().zip(-r..=r) {
+                *state = row[(col + offset).rem_euclid(width) as usize];
+            }
+            self.rule.next_state(&neighborhood)
+        }).collect();
+        self.history.push_back(next);
+        if self.history.len() > self.height {
+            self.history.pop_front();
+        }
+        self.refresh_live_cell_list();
+    }
+
+    fn rule(&self) -> String {
+        self.rule.to_string()
+    }
+
+    /// Cells in states the new rule does not have are cleared from the current row.
+    fn set_rule(&mut self, rule: &str) -> Result<(), String> {
+        self.rule = Rule1D::parse(rule)?;
+        let states = self.rule.states();
+        self.current_row().iter_mut().filter(|state| **state >= states).for_each(|state| *state = 0);
+        self.refresh_live_cell_list();
+        Ok(())
+    }
+
+    fn state_count(&self) -> u8 {
+        self.rule.states()
+    }
+
+    fn get_live_cells(&self) -> &[(f32,f32,f32)] {
+        &self.live_cells
+    }
+
+    fn toggle_cell(&mut self, row: usize, col: usize) {
+        self.toggle_cells(&[(row, col)]);
+    }
+
+    /// Only the current generation can change, so a cell anywhere in the diagram
+    /// toggles the cell in its column of the last row, stepping through the states.
+    fn toggle_cells(&mut self, cells: &[(usize, usize)]) {
+        let states = self.rule.states();
+        for (_, col) in cells {
+            let state = &mut self.current_row()[*col];
+            *state = (*state + 1) % states;
+        }
+        self.refresh_live_cell_list();
+    }
+
+    /// Starts a new diagram whose first row has the columns of the given cells alive.
+    fn set_cells(&mut self, cells: &[(usize, usize)]) {
+        let mut row = vec![0; self.width];
+        for (_, col) in cells {
+            row[*col] = 1;
+        }
+        self.history = VecDeque::from([row]);
+        self.refresh_live_cell_list();
+    }
+
+    fn set_size(&mut self, width: Option<usize>, height: Option<usize>) {
+        if let Some(w) = width {
+            self.width = w;
+        }
+        if let Some(h) = height {
+            self.height = h;
+        }
+        if width.is_some() || height.is_some() {
+            self.generation = 0;
+            self.set_cells(&[(0, self.width / 2)]);
+        }
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Elementary, Rule1D};
+    use crate::simulation::Simulation;
+
+    #[test]
+    fn parses_rules() {
+        assert_eq!(Rule1D::parse("30").unwrap(), Rule1D::Elementary(30));
+        assert_eq!(Rule1D::parse("w110").unwrap().to_string(), "W110");
+        assert_eq!(Rule1D::parse("T1599,K3").unwrap().to_string(), "T1599,K3,R1");
+        assert_eq!(Rule1D::parse("T20,R2").unwrap().range(), 2);
+        assert!(Rule1D::parse("W256").is_err());
+        // Two state range one codes have four digits
+        assert!(Rule1D::parse("T16").is_err());
+        assert!(Rule1D::parse("T3,K11").is_err());
+    }
+
+    #[test]
+    fn rule_90_draws_a_sierpinski_triangle() {
+        let mut universe = Elementary::new(33, 8);
+        universe.set_rule("W90").unwrap();
+        for _ in 0..7 {
+            universe.tick();
+        }
+        // Row n of Pascal's triangle mod 2, centered on the starting cell
+        for (n, row) in universe.history.iter().enumerate() {
+            for (col, state) in row.iter().enumerate() {
+                let k = col as isize - 16 + n as isize;
+                let alive = k >= 0 && k % 2 == 0 && k as usize / 2 <= n && (n & (k as usize / 2)) == k as usize / 2;
+                assert_eq!(*state == 1, alive, "generation {} column {}", n, col);
+            }
+        }
+    }
+
+    #[test]
+    fn diagram_scrolls_once_full() {
+        let mut universe = Elementary::new(20, 5);
+        for _ in 0..12 {
+            universe.tick();
+        }
+        assert_eq!(universe.generation(), 12);
+        assert_eq!(universe.history.len(), 5);
+        assert!(universe.get_live_cells().iter().all(|(row, _, _)| *row < 5.0));
+    }
+
+    #[test]
+    fn totalistic_rule_matches_elementary_rule() {
+        // Totalistic code 6 is alive on sums 1 and 2 only, as is elementary rule 126
+        let mut totalistic = Elementary::new(41, 20);
+        totalistic.set_rule("T6").unwrap();
+        let mut elementary = Elementary::new(41, 20);
+        elementary.set_rule("W126").unwrap();
+        for _ in 0..19 {
+            totalistic.tick();
+            elementary.tick();
+        }
+        assert_eq!(totalistic.get_live_cells(), elementary.get_live_cells());
+    }
+}
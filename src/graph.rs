@@ -0,0 +1,359 @@
+use std::any::Any;
+use std::collections::HashMap;
+
+use crate::random;
+use crate::rule::Rule;
+use crate::simulation::{Polygon, Simulation};
+
+// Side of a triangle and about the side of a rhomb, in cells
+const TILE_SIDE: f32 = 2.0;
+// Most times the Penrose triangles are cut into smaller ones
+const MAX_DEFLATIONS: usize = 9;
+// Corners closer than this many cells are the same corner
+const CORNER_PRECISION: f32 = 1e-3;
+
+// A corner rounded to `CORNER_PRECISION`, so corners computed twice match
+type Corner = (i64, i64);
+
+/// The shape of the tiles of a graph universe.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Tiling {
+    /// Equilateral triangles in rows, pointing up and down in turn.
+    Triangular,
+    /// The aperiodic Penrose tiling of thick and thin rhombs, filling a decagon.
+    Penrose,
+}
+
+impl Tiling {
+
+    /// Parses the name of a tiling as used by the settings bar, e.g. "penrose".
+    pub fn parse(name: &str) -> Result<Tiling, String> {
+        match name.trim().to_ascii_lowercase().as_str() {
+            "triangular" => Ok(Tiling::Triangular),
+            "penrose" => Ok(Tiling::Penrose),
+            _ => Err(format!("'{}' is not a tiling", name)),
+        }
+    }
+
+    /// Gets the corners of every tile fitting in a universe, as (row, column) positions.
+    fn polygons(&self, width: usize, height: usize) -> Vec<Polygon> {
+        match self {
+            Tiling::Triangular => Tiling::triangles(width as f32, height as f32),
+            Tiling::Penrose => Tiling::rhombs(width as f32, height as f32),
+        }
+    }
+
+    fn triangles(width: f32, height: f32) -> Vec<Polygon> {
+        let row_height = TILE_SIDE * 3f32.sqrt() / 2.0;
+        let mut triangles = Vec::new();
+        let mut top = 0.0;
+        for row in 0.. {
+            if top + row_height > height {
+                break;
+            }
+            let bottom = top + row_height;
+            let mut left = 0.0;
+            for col in 0.. {
+                if left + TILE_SIDE > width {
+                    break;
+                }
+                let (middle, right) = (left + TILE_SIDE / 2.0, left + TILE_SIDE);
+                triangles.push(if (row + col) % 2 == 0 {
+                    vec![(bottom, left), (bottom, right), (top, middle)]
+                } else {
+                    vec![(top, left), (top, right), (bottom, middle)]
+                });
+                left = middle;
+            }
+            top = bottom;
+        }
+        triangles
+    }
+
+    /// Builds a Penrose tiling by cutting a wheel of ten Robinson triangles into
+    /// smaller ones until their sides are about `TILE_SIDE`, then joining the
+    /// triangles sharing a base into rhombs. Halves at the rim are left out.
+    fn rhombs(width: f32, height: f32) -> Vec<Polygon> {
+        let golden = (1.0 + 5f64.sqrt()) / 2.0;
+        let radius = width.min(height) as f64 / 2.0;
+        let deflations = ((radius / TILE_SIDE as f64).ln() / golden.ln()).ceil().clamp(0.0, MAX_DEFLATIONS as f64) as usize;
+        // Thin (false) and thick (true) triangles with apex a and base b to c, as (row, column)
+        type Triangle = (bool, (f64, f64), (f64, f64), (f64, f64));
+        let point = |angle: f64| (radius * angle.sin(), radius * angle.cos());
+        let lerp = |p: (f64, f64), q: (f64, f64), t: f64| (p.0 + (q.0 - p.0) * t, p.1 + (q.1 - p.1) * t);
+        let mut triangles: Vec<Triangle> = (0..10).map(|i| {
+            let b = point((2 * i - 1) as f64 * std::f64::consts::PI / 10.0);
+            let c = point((2 * i + 1) as f64 * std::f64::consts::PI / 10.0);
+            // Every second triangle is mirrored so neighbors share their bases
+            if i % 2 == 0 { (false, (0.0, 0.0), c, b) } else { (false, (0.0, 0.0), b, c) }
+        }).collect();
+        for _ in 0..deflations {
+            triangles = triangles.into_iter().flat_map(|(thick, a, b, c)| {
+                if !thick {
+                    let p = lerp(a, b, 1.0 / golden);
+                    vec![(false, c, p, b), (true, p, c, a)]
+                } else {
+                    let q = lerp(b, a, 1.0 / golden);
+                    let r = lerp(b, c, 1.0 / golden);
+                    vec![(true, r, c, a), (true, q, r, b), (false, r, q, a)]
+                }
+            }).collect();
+        }
+        let key = |p: (f64, f64)| ((p.0 / CORNER_PRECISION as f64).round() as i64, (p.1 / CORNER_PRECISION as f64).round() as i64);
+        let mut halves: HashMap<(Corner, Corner), Vec<usize>> = HashMap::new();
+        for (i, (_, _, b, c)) in triangles.iter().enumerate() {
+            let (b, c) = (key(*b), key(*c));
+            halves.entry((b.min(c), b.max(c))).or_default().push(i);
+        }
+        let center = (height as f64 / 2.0, width as f64 / 2.0);
+        let place = |p: (f64, f64)| ((center.0 + p.0) as f32, (center.1 + p.1) as f32);
+        let mut pairs: Vec<&Vec<usize>> = halves.values().filter(|pair| pair.len() == 2).collect();
+        // Keep the order of the triangles, the hash map has none
+        pairs.sort();
+        pairs.into_iter().map(|pair| {
+            let (_, a1, b, c) = triangles[pair[0]];
+            let (_, a2, _, _) = triangles[pair[1]];
+            vec![place(a1), place(b), place(a2), place(c)]
+        }).collect()
+    }
+}
+
+/// A universe whose cells are the tiles of a tiling, each a node of a graph
+/// joined to the tiles it shares a corner with.
+///
+/// Life-like rules count live neighbors through the graph instead of the eight
+/// cells around a square, so the same B/S rules run on triangles or on the
+/// aperiodic Penrose tiling. The renderer draws the tiles as polygons.
+pub struct Graph {
+    width: usize,
+    height: usize,
+    generation: u64,
+    rule: Rule,
+    tiling: Tiling,
+    polygons: Vec<Polygon>,
+    centers: Vec<(f32, f32)>,
+    neighbors: Vec<Vec<usize>>,
+    states: Vec<u8>,
+    live_cells: Vec<(f32,f32,f32)>
+}
+
+impl Graph {
+
+    /// Creates a Penrose tiling running B3/S23 with random live tiles.
+    pub fn new(width: usize, height: usize) -> Graph {
+        let mut graph = Graph {
+            width,
+            height,
+            generation: 0,
+            rule: Rule::default(),
+            tiling: Tiling::Penrose,
+            polygons: Vec::new(),
+            centers: Vec::new(),
+            neighbors: Vec::new(),
+            states: Vec::new(),
+            live_cells: Vec::new()
+        };
+        graph.build();
+        graph
+    }
+
+    pub fn set_tiling(&mut self, tiling: Tiling) {
+        self.tiling = tiling;
+        self.generation = 0;
+        self.build();
+    }
+
+    /// Lays out the tiles, joins every pair of tiles sharing a corner and fills about a third of them.
+    fn build(&mut self) {
+        self.polygons = self.tiling.polygons(self.width, self.height);
+        self.centers = self.polygons.iter().map(|polygon| {
+            let n = polygon.len() as f32;
+            let (rows, cols): (Vec<f32>, Vec<f32>) = polygon.iter().copied().unzip();
+            (rows.iter().sum::<f32>() / n, cols.iter().sum::<f32>() / n)
+        }).collect();
+        let mut corners: HashMap<Corner, Vec<usize>> = HashMap::new();
+        for (tile, polygon) in self.polygons.iter().enumerate() {
+            for (row, col) in polygon {
+                let key = ((row / CORNER_PRECISION).round() as i64, (col / CORNER_PRECISION).round() as i64);
+                corners.entry(key).or_default().push(tile);
+            }
+        }
+        self.neighbors = vec![Vec::new(); self.polygons.len()];
+        for tiles in corners.values() {
+            for a in tiles {
+                self.neighbors[*a].extend(tiles.iter().filter(|b| *b != a));
+            }
+        }
+        for neighbors in self.neighbors.iter_mut() {
+            neighbors.sort_unstable();
+            neighbors.dedup();
+        }
+        self.states = (0..self.polygons.len()).map(|idx| (random::unit(7, 0, idx, 0) < 0.375) as u8).collect();
+        self.refresh_live_cell_list();
+    }
+
+    /// Gets the tile whose center is nearest the middle of a grid cell.
+    fn tile_at(&self, row: usize, col: usize) -> Option<usize> {
+        let (row, col) = (row as f32 + 0.5, col as f32 + 0.5);
+        let distance = |(r, c): &(f32, f32)| (r - row) * (r - row) + (c - col) * (c - col);
+        (0..self.centers.len())
+            .min_by(|a, b| distance(&self.centers[*a]).total_cmp(&distance(&self.centers[*b])))
+            .filter(|tile| distance(&self.centers[*tile]) <= TILE_SIDE * TILE_SIDE)
+    }
+
+    fn refresh_live_cell_list(&mut self) {
+        self.live_cells.clear();
+        for (state, (row, col)) in self.states.iter().zip(self.centers.iter()) {
+            if *state != 0 {
+                self.live_cells.push((*row, *col, *state as f32));
+            }
+        }
+    }
+}
+
+impl Simulation for Graph {
+    fn width(&self) -> usize {
+        self.width
+    }
+
+    fn height(&self) -> usize {
+        self.height
+    }
+
+    fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    fn tick(&mut self) {
+        self.generation += 1;
+        self.states = self.neighbors.iter().zip(self.states.iter()).map(|(neighbors, state)| {
+            let live = neighbors.iter().filter(|tile| self.states[**tile] == 1).count();
+            self.rule.next_state(*state, live)
+        }).collect();
+        self.refresh_live_cell_list();
+    }
+
+    fn rule(&self) -> String {
+        self.rule.to_string()
+    }
+
+    /// Takes B/S and Generations rules, which only count live neighbors.
+    fn set_rule(&mut self, rule: &str) -> Result<(), String> {
+        let rule = Rule::parse(rule)?;
+        if !rule.counts_neighbors_only() {
+            return Err(format!("rule '{}' needs a grid, tiles only count their live neighbors", rule));
+        }
+        let states = rule.states();
+        self.states.iter_mut().filter(|state| **state >= states).for_each(|state| *state = 0);
+        self.rule = rule;
+        self.refresh_live_cell_list();
+        Ok(())
+    }
+
+    fn state_count(&self) -> u8 {
+        self.rule.states()
+    }
+
+    /// Gets the centers of the live tiles.
+    fn get_live_cells(&self) -> &[(f32,f32,f32)] {
+        &self.live_cells
+    }
+
+    fn toggle_cell(&mut self, row: usize, col: usize) {
+        self.toggle_cells(&[(row, col)]);
+    }
+
+    /// Toggles the tiles nearest the given cells, a dying tile is toggled to dead.
+    fn toggle_cells(&mut self, cells: &[(usize, usize)]) {
+        for (row, col) in cells {
+            if let Some(tile) = self.tile_at(*row, *col) {
+                self.states[tile] = if self.states[tile] == 0 { 1 } else { 0 };
+            }
+        }
+        self.refresh_live_cell_list();
+    }
+
+    /// Brings the tiles nearest the given cells to life and clears every other tile.
+    fn set_cells(&mut self, cells: &[(usize, usize)]) {
+        self.states.iter_mut().for_each(|state| *state = 0);
+        for (row, col) in cells {
+            if let Some(tile) = self.tile_at(*row, *col) {
+                self.states[tile] = 1;
+            }
+        }
+        self.refresh_live_cell_list();
+    }
+
+    fn set_size(&mut self, width: Option<usize>, height: Option<usize>) {
+        if let Some(w) = width {
+            self.width = w;
+        }
+        if let Some(h) = height {
+            self.height = h;
+        }
+        if width.is_some() || height.is_some() {
+            self.generation = 0;
+            self.build();
+        }
+    }
+
+    fn polygons(&self) -> Option<(&[Polygon], &[u8])> {
+        Some((&self.polygons, &self.states))
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Graph, Tiling};
+    use crate::simulation::Simulation;
+
+    #[test]
+    fn triangles_have_twelve_neighbors_inside() {
+        let mut graph = Graph::new(40, 30);
+        graph.set_tiling(Tiling::Triangular);
+        assert!(graph.polygons.iter().all(|polygon| polygon.len() == 3));
+        assert_eq!(graph.neighbors.iter().map(|n| n.len()).max(), Some(12));
+        // Corner tiles only touch a few others
+        assert!(graph.neighbors[0].len() < 12);
+        assert!(graph.polygons.iter().flatten().all(|(row, col)| *row <= 30.0 && *col <= 40.0));
+    }
+
+    #[test]
+    fn penrose_rhombs_have_equal_sides() {
+        let graph = Graph::new(60, 60);
+        assert!(graph.polygons.len() > 100);
+        for polygon in graph.polygons.iter() {
+            assert_eq!(polygon.len(), 4);
+            let side = |i: usize| {
+                let ((r1, c1), (r2, c2)) = (polygon[i], polygon[(i + 1) % 4]);
+                ((r1 - r2).powi(2) + (c1 - c2).powi(2)).sqrt()
+            };
+            assert!((0..4).all(|i| (side(i) - side(0)).abs() < 1e-3));
+        }
+        // Every tile of a Penrose tiling meets between 7 and 11 others at its corners, fewer at the rim
+        assert!(graph.neighbors.iter().all(|n| n.len() <= 11));
+        assert!(graph.neighbors.iter().filter(|n| n.len() >= 7).count() > graph.polygons.len() / 2);
+    }
+
+    #[test]
+    fn rules_count_neighbors_through_the_graph() {
+        let mut graph = Graph::new(40, 30);
+        graph.set_tiling(Tiling::Triangular);
+        graph.set_rule("B1/S").unwrap();
+        let middle = graph.neighbors.iter().position(|n| n.len() == 12).unwrap();
+        let (row, col) = graph.centers[middle];
+        graph.set_cells(&[(row as usize, col as usize)]);
+        assert_eq!(graph.get_live_cells().len(), 1);
+        let lit = graph.states.iter().position(|state| *state == 1).unwrap();
+        graph.tick();
+        let mut alive: Vec<usize> = (0..graph.states.len()).filter(|tile| graph.states[*tile] == 1).collect();
+        alive.sort_unstable();
+        assert_eq!(alive, graph.neighbors[lit]);
+        assert!(graph.set_rule("B2-a/S12").is_err());
+        assert!(graph.set_rule("B2/S/C3").is_ok());
+    }
+}
@@ -0,0 +1,542 @@
+use std::any::Any;
+use std::collections::HashMap;
+
+use crate::rule::Rule;
+use crate::simulation::Simulation;
+
+type NodeId = u32;
+
+const DEAD: NodeId = 0;
+const ALIVE: NodeId = 1;
+const MIN_LEVEL: u8 = 3;
+// Rebuild the node store from the root once it holds this many nodes and memoized results
+const GC_NODE_LIMIT: usize = 1 << 22;
+
+/// A square quadtree node covering 2^level by 2^level cells.
+///
+/// Level 0 nodes are single cells, every other node is made of four children
+/// one level below it.
+#[derive(Clone, Copy)]
+struct Node {
+    level: u8,
+    nw: NodeId,
+    ne: NodeId,
+    sw: NodeId,
+    se: NodeId,
+    population: u64,
+}
+
+/// A HashLife engine on an unbounded plane.
+///
+/// Every distinct quadtree node is stored once, and the result of advancing a
+/// node is memoized, so large regular patterns can be stepped 2^k generations
+/// in roughly the time of a single step. The `width` by `height` window starting
+/// at the origin is what gets drawn and edited.
+pub struct HashLife {
+    width: usize,
+    height: usize,
+    generation: u64,
+    rule: Rule,
+    nodes: Vec<Node>,
+    lookup: HashMap<(NodeId, NodeId, NodeId, NodeId), NodeId>,
+    results: HashMap<(NodeId, u8), NodeId>,
+    empty: Vec<NodeId>,
+    root: NodeId,
+    live_cells: Vec<(f32,f32,f32)>
+}
+
+impl HashLife {
+
+    pub fn new(width: usize, height: usize) -> HashLife {
+        let mut hashlife = HashLife {
+            width,
+            height,
+            generation: 0,
+            rule: Rule::default(),
+            nodes: Vec::new(),
+            lookup: HashMap::new(),
+            results: HashMap::new(),
+            empty: Vec::new(),
+            root: DEAD,
+            live_cells: Vec::new()
+        };
+        hashlife.reset_nodes();
+        // Start with a glider when the window is big enough to hold it
+        if width >= 4 && height >= 4 {
+            let (row, col) = (height / 4, width / 4);
+            hashlife.set_cells(&[(row, col), (row, col + 1), (row - 1, col), (row - 1, col - 1), (row + 1, col - 1)]);
+        }
+        hashlife
+    }
+
+    /// Clears every node and memoized result and starts from an empty root.
+    fn reset_nodes(&mut self) {
+        self.nodes.clear();
+        self.lookup.clear();
+        self.results.clear();
+        self.empty.clear();
+        let leaf = |population| Node { level: 0, nw: DEAD, ne: DEAD, sw: DEAD, se: DEAD, population };
+        self.nodes.push(leaf(0));
+        self.nodes.push(leaf(1));
+        self.empty.push(DEAD);
+        self.root = self.empty_node(MIN_LEVEL);
+    }
+
+    /// Gets the canonical node made of four children.
+    fn join(&mut self, nw: NodeId, ne: NodeId, sw: NodeId, se: NodeId) -> NodeId {
+        if let Some(id) = self.lookup.get(&(nw, ne, sw, se)) {
+            return *id;
+        }
+        let n = &self.nodes;
+        let node = Node {
+            level: n[nw as usize].level + 1,
+            nw,
+            ne,
+            sw,
+            se,
+            population: n[nw as usize].population + n[ne as usize].population
+                + n[sw as usize].population + n[se as usize].population,
+        };
+        let id = self.nodes.len() as NodeId;
+        self.nodes.push(node);
+        self.lookup.insert((nw, ne, sw, se), id);
+        id
+    }
+
+    fn empty_node(&mut self, level: u8) -> NodeId {
+        while self.empty.len() <= level as usize {
+            let e = *self.empty.last().unwrap();
+            let id = self.join(e, e, e, e);
+            self.empty.push(id);
+        }
+        self.empty[level as usize]
+    }
+
+    fn node(&self, id: NodeId) -> Node {
+        self.nodes[id as usize]
+    }
+
+    /// Gets the node twice the size of `id` with `id` in its center.
+    fn expand(&mut self, id: NodeId) -> NodeId {
+        let n = self.node(id);
+        let e = self.empty_node(n.level - 1);
+        let nw = self.join(e, e, e, n.nw);
+        let ne = self.join(e, e, n.ne, e);
+        let sw = self.join(e, n.sw, e, e);
+        let se = self.join(n.se, e, e, e);
+        self.join(nw, ne, sw, se)
+    }
+
+    /// Gets whether every live cell of a node sits in its central quarter, which
+    /// keeps the pattern from outrunning the node while it is advanced.
+    fn is_padded(&self, id: NodeId) -> bool {
+        let n = self.node(id);
+        let (nw, ne, sw, se) = (self.node(n.nw), self.node(n.ne), self.node(n.sw), self.node(n.se));
+        nw.population == self.node(self.node(nw.se).se).population
+            && ne.population == self.node(self.node(ne.sw).sw).population
+            && sw.population == self.node(self.node(sw.ne).ne).population
+            && se.population == self.node(self.node(se.nw).nw).population
+    }
+
+    /// Gets the center half of a level 2 node advanced one generation by brute force.
+    fn advance_level_2(&mut self, id: NodeId) -> NodeId {
+        let n = self.node(id);
+        let mut cells = [[0u8; 4]; 4];
+        for (quadrant, (row, col)) in [(n.nw, (0, 0)), (n.ne, (0, 2)), (n.sw, (2, 0)), (n.se, (2, 2))] {
+            let q = self.node(quadrant);
+            cells[row][col] = q.nw as u8;
+            cells[row][col + 1] = q.ne as u8;
+            cells[row + 1][col] = q.sw as u8;
+            cells[row + 1][col + 1] = q.se as u8;
+        }
+        let mut next = [DEAD; 4];
+        for (i, (row, col)) in [(1, 1), (1, 2), (2, 1), (2, 2)].iter().enumerate() {
+            let block: usize = cells[row - 1..=row + 1].iter()
+                .map(|r| r[col - 1..=col + 1].iter().map(|c| *c as usize).sum::<usize>())
+                .sum();
+            let count = block - cells[*row][*col] as usize;
+            next[i] = self.rule.next_state(cells[*row][*col], count) as NodeId;
+        }
+        self.join(next[0], next[1], next[2], next[3])
+    }
+
+    /// Gets the center half of a node advanced 2^step generations.
+    ///
+    /// `step` can be at most `level - 2`, anything larger is clamped to that.
+    fn advance(&mut self, id: NodeId, step: u8) -> NodeId {
+        let n = self.node(id);
+        if n.population == 0 {
+            return self.empty_node(n.level - 1);
+        }
+        if n.level == 2 {
+            return self.advance_level_2(id);
+        }
+        let step = step.min(n.level - 2);
+        if let Some(result) = self.results.get(&(id, step)) {
+            return *result;
+        }
+        let (nw, ne, sw, se) = (self.node(n.nw), self.node(n.ne), self.node(n.sw), self.node(n.se));
+        // Nine overlapping nodes one level down tile the center of this node
+        let parts = [
+            n.nw,
+            self.join(nw.ne, ne.nw, nw.se, ne.sw),
+            n.ne,
+            self.join(nw.sw, nw.se, sw.nw, sw.ne),
+            self.join(nw.se, ne.sw, sw.ne, se.nw),
+            self.join(ne.sw, ne.se, se.nw, se.ne),
+            n.sw,
+            self.join(sw.ne, se.nw, sw.se, se.sw),
+            n.se,
+        ];
+        let mut c = [DEAD; 9];
+        for (i, part) in parts.iter().enumerate() {
+            c[i] = self.advance(*part, step);
+        }
+        let result = if step < n.level - 2 {
+            // The nine results have already moved 2^step generations, only their centers are kept
+            let mut centers = [DEAD; 4];
+            for (i, (a, b, d, e)) in [(0, 1, 3, 4), (1, 2, 4, 5), (3, 4, 6, 7), (4, 5, 7, 8)].iter().enumerate() {
+                let (a, b, d, e) = (self.node(c[*a]), self.node(c[*b]), self.node(c[*d]), self.node(c[*e]));
+                centers[i] = self.join(a.se, b.sw, d.ne, e.nw);
+            }
+            self.join(centers[0], centers[1], centers[2], centers[3])
+        }
+        else {
+            // Each half of the full step comes from one round of advancing
+            let mut quads = [DEAD; 4];
+            for (i, (a, b, d, e)) in [(0, 1, 3, 4), (1, 2, 4, 5), (3, 4, 6, 7), (4, 5, 7, 8)].iter().enumerate() {
+                let joined = self.join(c[*a], c[*b], c[*d], c[*e]);
+                quads[i] = self.advance(joined, step);
+            }
+            self.join(quads[0], quads[1], quads[2], quads[3])
+        };
+        self.results.insert((id, step), result);
+        result
+    }
+
+    /// Advance the root 2^step generations.
+    fn advance_root(&mut self, step: u8) {
+        let mut root = self.root;
+        while self.node(root).level < step + 2 || !self.is_padded(root) {
+            root = self.expand(root);
+        }
+        // One more level so the result, half the size, still holds the whole pattern
+        root = self.expand(root);
+        self.root = self.advance(root, step);
+        self.generation += 1 << step;
+    }
+
+    /// Gets the row and column offset of the root's top left corner from the origin.
+    fn root_origin(&self) -> i64 {
+        -(1i64 << (self.node(self.root).level - 1))
+    }
+
+    fn get_cell(&self, row: i64, col: i64) -> bool {
+        let origin = self.root_origin();
+        let (mut row, mut col) = (row - origin, col - origin);
+        let mut id = self.root;
+        let size = 1i64 << self.node(id).level;
+        if row < 0 || col < 0 || row >= size || col >= size {
+            return false;
+        }
+        while self.node(id).level > 0 {
+            let n = self.node(id);
+            let half = 1i64 << (n.level - 1);
+            id = match (row >= half, col >= half) {
+                (false, false) => n.nw,
+                (false, true) => n.ne,
+                (true, false) => n.sw,
+                (true, true) => n.se,
+            };
+            row %= half;
+            col %= half;
+        }
+        id == ALIVE
+    }
+
+    fn set_cell(&mut self, row: i64, col: i64, alive: bool) {
+        loop {
+            let half = 1i64 << (self.node(self.root).level - 1);
+            if row >= -half && row < half && col >= -half && col < half {
+                break;
+            }
+            self.root = self.expand(self.root);
+        }
+        let origin = self.root_origin();
+        self.root = self.set_cell_in(self.root, row - origin, col - origin, alive);
+    }
+
+    fn set_cell_in(&mut self, id: NodeId, row: i64, col: i64, alive: bool) -> NodeId {
+        let n = self.node(id);
+        if n.level == 0 {
+            return if alive { ALIVE } else { DEAD };
+        }
+        let half = 1i64 << (n.level - 1);
+        let (mut nw, mut ne, mut sw, mut se) = (n.nw, n.ne, n.sw, n.se);
+        match (row >= half, col >= half) {
+            (false, false) => nw = self.set_cell_in(nw, row, col, alive),
+            (false, true) => ne = self.set_cell_in(ne, row, col - half, alive),
+            (true, false) => sw = self.set_cell_in(sw, row - half, col, alive),
+            (true, true) => se = self.set_cell_in(se, row - half, col - half, alive),
+        }
+        self.join(nw, ne, sw, se)
+    }
+
+    fn refresh_live_cell_list(&mut self) {
+        self.live_cells.clear();
+        let origin = self.root_origin();
+        let mut cells = Vec::new();
+        self.collect_cells(self.root, origin, origin, &mut cells);
+        self.live_cells = cells;
+    }
+
+    /// Pushes every live cell of a node that lies inside the drawn window.
+    fn collect_cells(&self, id: NodeId, row: i64, col: i64, cells: &mut Vec<(f32,f32,f32)>) {
+        let n = self.node(id);
+        let size = 1i64 << n.level;
+        if n.population == 0 || row >= self.height as i64 || col >= self.width as i64 || row + size <= 0 || col + size <= 0 {
+            return;
+        }
+        if n.level == 0 {
+            cells.push((row as f32, col as f32, 1.0));
+            return;
+        }
+        let half = size / 2;
+        self.collect_cells(n.nw, row, col, cells);
+        self.collect_cells(n.ne, row, col + half, cells);
+        self.collect_cells(n.sw, row + half, col, cells);
+        self.collect_cells(n.se, row + half, col + half, cells);
+    }
+
+    /// Rebuilds the node store with only the nodes reachable from the root or an
+    /// empty node, and the memoized results between those nodes.
+    ///
+    /// A node is always stored after its children, so copying the reachable nodes
+    /// in the order of their old ids gives every child its new id before its parents.
+    fn collect_garbage(&mut self) {
+        let mut reachable = vec![false; self.nodes.len()];
+        let mut stack = self.empty.clone();
+        stack.extend([ALIVE, self.root]);
+        while let Some(id) = stack.pop() {
+            if reachable[id as usize] {
+                continue;
+            }
+            reachable[id as usize] = true;
+            let n = self.node(id);
+            if n.level > 0 {
+                stack.extend([n.nw, n.ne, n.sw, n.se]);
+            }
+        }
+
+        let mut remap = vec![DEAD; self.nodes.len()];
+        let mut nodes = Vec::new();
+        let mut lookup = HashMap::new();
+        for (id, node) in self.nodes.iter().enumerate() {
+            if !reachable[id] {
+                continue;
+            }
+            let mut node = *node;
+            remap[id] = nodes.len() as NodeId;
+            if node.level > 0 {
+                (node.nw, node.ne, node.sw, node.se) = (
+                    remap[node.nw as usize], remap[node.ne as usize], remap[node.sw as usize], remap[node.se as usize]
+                );
+                lookup.insert((node.nw, node.ne, node.sw, node.se), remap[id]);
+            }
+            nodes.push(node);
+        }
+        self.results = self.results.drain()
+            .filter(|((id, _), result)| reachable[*id as usize] && reachable[*result as usize])
+            .map(|((id, step), result)| ((remap[id as usize], step), remap[result as usize]))
+            .collect();
+        self.nodes = nodes;
+        self.lookup = lookup;
+        self.empty.iter_mut().for_each(|id| *id = remap[*id as usize]);
+        self.root = remap[self.root as usize];
+    }
+}
+
+impl Simulation for HashLife {
+    fn width(&self) -> usize {
+        self.width
+    }
+
+    fn height(&self) -> usize {
+        self.height
+    }
+
+    fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    fn tick(&mut self) {
+        self.step(1);
+    }
+
+    fn can_jump(&self) -> bool {
+        true
+    }
+
+    /// Steps by each power of two in `generations`, so a jump of 2^k takes one advance.
+    fn step(&mut self, generations: u64) {
+        for step in 0..64 {
+            if generations & (1 << step) != 0 {
+                self.advance_root(step);
+                if self.nodes.len() + self.results.len() > GC_NODE_LIMIT {
+                    self.collect_garbage();
+                }
+            }
+        }
+        self.refresh_live_cell_list();
+    }
+
+    fn rule(&self) -> String {
+        self.rule.to_string()
+    }
+
+    fn set_rule(&mut self, rule: &str) -> Result<(), String> {
+        let rule = Rule::parse(rule)?;
+        if !rule.is_life_like() {
+            return Err(String::from("HashLife only runs two state rules on the eight cell neighborhood"));
+        }
+        if rule.next_state(0, 0) == 1 {
+            return Err(String::from("HashLife cannot run rules where cells are born with no neighbors"));
+        }
+        self.rule = rule;
+        // Memoized results were computed with the old rule
+        self.results.clear();
+        Ok(())
+    }
+
+    fn state_count(&self) -> u8 {
+        2
+    }
+
+    fn get_live_cells(&self) -> &[(f32,f32,f32)] {
+        &self.live_cells
+    }
+
+    fn toggle_cell(&mut self, row: usize, col: usize) {
+        self.toggle_cells(&[(row, col)]);
+    }
+
+    fn toggle_cells(&mut self, cells: &[(usize, usize)]) {
+        for (row, col) in cells {
+            let (row, col) = (*row as i64, *col as i64);
+            let alive = self.get_cell(row, col);
+            self.set_cell(row, col, !alive);
+        }
+        self.refresh_live_cell_list();
+    }
+
+    fn set_cells(&mut self, cells: &[(usize, usize)]) {
+        self.reset_nodes();
+        for (row, col) in cells {
+            self.set_cell(*row as i64, *col as i64, true);
+        }
+        self.refresh_live_cell_list();
+    }
+
+    fn set_size(&mut self, width: Option<usize>, height: Option<usize>) {
+        if let Some(w) = width {
+            self.width = w;
+        }
+        if let Some(h) = height {
+            self.height = h;
+        }
+        if width.is_some() || height.is_some() {
+            self.generation = 0;
+            self.set_cells(&[]);
+        }
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::HashLife;
+    use crate::simulation::Simulation;
+    use crate::universe::Universe;
+
+    #[test]
+    fn matches_grid_engine() {
+        // A glider and an r-pentomino, far enough from the edges not to wrap
+        let cells = [(5, 6), (5, 7), (4, 6), (3, 5), (6, 5), (40, 41), (40, 42), (41, 40), (41, 41), (42, 41)];
+        let mut grid = Universe::new(80, 80);
+        grid.set_cells(&cells);
+        let mut hashlife = HashLife::new(80, 80);
+        hashlife.set_cells(&cells);
+        for _ in 0..30 {
+            grid.tick();
+            hashlife.tick();
+        }
+        let mut expected = grid.get_live_cells().to_vec();
+        let mut actual = hashlife.get_live_cells().to_vec();
+        expected.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        actual.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        assert_eq!(expected, actual);
+
+        // Jumping 2^5 generations at once lands on the same cells as 32 single steps
+        hashlife.step(32);
+        for _ in 0..32 {
+            grid.tick();
+        }
+        let mut expected = grid.get_live_cells().to_vec();
+        let mut actual = hashlife.get_live_cells().to_vec();
+        expected.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        actual.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        assert_eq!(expected, actual);
+    }
+
+    #[test]
+    fn jumps_far_ahead() {
+        let mut hashlife = HashLife::new(100, 100);
+        assert!(hashlife.can_jump());
+        assert!(!Universe::new(100, 100).can_jump());
+        hashlife.step(1 << 30);
+        assert_eq!(hashlife.generation(), 1 << 30);
+        // The glider keeps its five cells however far it travels
+        assert_eq!(hashlife.node(hashlife.root).population, 5);
+    }
+
+    #[test]
+    fn garbage_collection_keeps_the_pattern_and_its_results() {
+        let mut hashlife = HashLife::new(100, 100);
+        hashlife.set_cells(&[(50, 51), (51, 53), (52, 50), (52, 51), (52, 54), (52, 55), (52, 56)]);
+        hashlife.step(1 << 8);
+        let (nodes, results) = (hashlife.nodes.len(), hashlife.results.len());
+        let cells = hashlife.get_live_cells().to_vec();
+        hashlife.collect_garbage();
+        assert!(hashlife.nodes.len() < nodes && hashlife.results.len() < results);
+        hashlife.refresh_live_cell_list();
+        assert_eq!(hashlife.get_live_cells(), &cells[..]);
+
+        // Stepping on from the collected store matches stepping on without collecting
+        let mut fresh = HashLife::new(100, 100);
+        fresh.set_cells(&[(50, 51), (51, 53), (52, 50), (52, 51), (52, 54), (52, 55), (52, 56)]);
+        fresh.step(1 << 8);
+        hashlife.step(1 << 8);
+        fresh.step(1 << 8);
+        assert_eq!(hashlife.node(hashlife.root).population, fresh.node(fresh.root).population);
+        assert_eq!(hashlife.get_live_cells(), fresh.get_live_cells());
+    }
+
+    #[test]
+    fn rejects_births_with_no_neighbors() {
+        let mut hashlife = HashLife::new(100, 100);
+        assert!(hashlife.set_rule("B0123478/S34678").is_err());
+        assert_eq!(hashlife.rule(), "B3/S23");
+        assert!(hashlife.set_rule("B36/S23").is_ok());
+    }
+
+    #[test]
+    fn starts_empty_when_too_small_for_a_glider() {
+        for (width, height) in [(1, 1), (3, 10), (10, 3)] {
+            assert!(HashLife::new(width, height).get_live_cells().is_empty());
+        }
+        assert_eq!(HashLife::new(4, 4).get_live_cells().len(), 5);
+    }
+}
@@ -0,0 +1,92 @@
+//! Hensel notation for the 3x3 neighborhoods of isotropic non-totalistic rules.
+//!
+//! A neighborhood is an 8 bit mask of the live cells around a cell, bit 0 to 7
+//! being the north west, north, north east, west, east, south west, south and
+//! south east neighbors. Rotating or mirroring a neighborhood keeps its letter.
+
+// Row and column offset of the cell for each bit of a neighborhood
+const OFFSETS: [(isize, isize); 8] = [
+    (-1, -1), (-1, 0), (-1, 1),
+    (0, -1),           (0, 1),
+    (1, -1),  (1, 0),  (1, 1),
+];
+
+/// The letters for each neighbor count, in the order rule strings are written.
+pub const LETTERS: [&str; 9] = [
+    "",
+    "ce",
+    "ceaikn",
+    "ceaiknjqry",
+    "ceaiknjqrytwz",
+    "ceaiknjqry",
+    "ceaikn",
+    "ce",
+    "",
+];
+
+// One neighborhood of each letter for up to four neighbors, in the order of `LETTERS`
+const REPRESENTATIVES: [&[u8]; 5] = [
+    &[],
+    &[1, 2],
+    &[5, 10, 3, 24, 17, 36],
+    &[37, 26, 11, 7, 50, 13, 14, 38, 25, 49],
+    &[165, 90, 15, 29, 51, 39, 58, 54, 27, 53, 57, 46, 60],
+];
+
+/// Gets every neighborhood with `count` live cells written with `letter`.
+pub fn neighborhoods(count: usize, letter: char) -> Option<Vec<u8>> {
+    let index = LETTERS.get(count)?.find(letter)?;
+    // Five or more neighbors are the complement of the same letter with the dead cells
+    let representative = if count <= 4 {
+        REPRESENTATIVES[count][index]
+    } else {
+        !REPRESENTATIVES[8 - count][index]
+    };
+    let mut masks: Vec<u8> = symmetries(representative).collect();
+    masks.sort_unstable();
+    masks.dedup();
+    Some(masks)
+}
+
+/// Gets the eight rotations and reflections of a neighborhood.
+fn symmetries(mask: u8) -> impl Iterator<Item = u8> {
+    (0..8).map(move |symmetry| {
+        let mut transformed = 0;
+        for (bit, (row, col)) in OFFSETS.iter().enumerate() {
+            if mask >> bit & 1 == 0 {
+                continue;
+            }
+            let (mut row, mut col) = (*row, *col);
+            for _ in 0..symmetry % 4 {
+                (row, col) = (col, -row);
+            }
+            if symmetry >= 4 {
+                col = -col;
+            }
+            let target = OFFSETS.iter().position(|offset| *offset == (row, col)).unwrap();
+            transformed |= 1 << target;
+        }
+        transformed
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{neighborhoods, LETTERS};
+
+    #[test]
+    fn letters_split_every_neighborhood_once() {
+        let mut seen = [false; 256];
+        for (count, letters) in LETTERS.iter().enumerate() {
+            for l in letters.chars() {
+                for mask in neighborhoods(count, l).unwrap() {
+                    assert_eq!(mask.count_ones() as usize, count);
+                    assert!(!seen[mask as usize], "{} is in more than one letter", mask);
+                    seen[mask as usize] = true;
+                }
+            }
+        }
+        // Only the empty and the full neighborhood have no letter
+        assert_eq!(seen.iter().filter(|s| !**s).count(), 2);
+    }
+}
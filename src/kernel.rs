@@ -0,0 +1,86 @@
+use std::fmt;
+
+const MAX_SIZE: usize = 7;
+const MAX_WEIGHT: i32 = 100;
+
+/// A square of integer weights centered on a cell, at most 7 by 7 with an odd side.
+///
+/// The weighted sum of a cell adds the weight of every live cell under the
+/// square, the cell itself included. Weights can be negative and the square
+/// does not need to be symmetric.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Kernel {
+    size: usize,
+    // Row major, the cell itself in the middle
+    weights: Vec<i32>,
+}
+
+impl Kernel {
+
+    /// Makes a kernel from its rows of weights.
+    pub fn from_rows(rows: &[Vec<i32>]) -> Result<Kernel, String> {
+        let size = rows.len();
+        if size.is_multiple_of(2) || size > MAX_SIZE {
+            return Err(format!("a kernel should have 1, 3, 5 or 7 rows, not {}", size));
+        }
+        if let Some(row) = rows.iter().find(|row| row.len() != size) {
+            return Err(format!("every kernel row should have {} weights, one has {}", size, row.len()));
+        }
+        if let Some(weight) = rows.iter().flatten().find(|w| w.abs() > MAX_WEIGHT) {
+            return Err(format!("weight {} is not between -{} and {}", weight, MAX_WEIGHT, MAX_WEIGHT));
+        }
+        Ok(Kernel { size, weights: rows.concat() })
+    }
+
+    /// Gets how far from a cell the kernel reaches.
+    pub fn radius(&self) -> usize {
+        self.size / 2
+    }
+
+    /// Gets the row and column offset from the middle and the weight of every
+    /// nonzero weight.
+    pub fn offsets(&self) -> impl Iterator<Item = (isize, isize, i32)> + '_ {
+        let r = self.radius() as isize;
+        self.weights.iter().enumerate()
+            .filter(|(_, weight)| **weight != 0)
+            .map(move |(i, weight)| ((i / self.size) as isize - r, (i % self.size) as isize - r, *weight))
+    }
+
+    /// Gets the smallest weighted sum, with only the negative weights alive.
+    pub fn min_sum(&self) -> i32 {
+        self.weights.iter().filter(|w| **w < 0).sum()
+    }
+
+    /// Gets the largest weighted sum, with only the positive weights alive.
+    pub fn max_sum(&self) -> i32 {
+        self.weights.iter().filter(|w| **w > 0).sum()
+    }
+}
+
+impl fmt::Display for Kernel {
+    /// Writes the rows separated by ';' and the weights of a row by ','.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let rows: Vec<String> = self.weights.chunks(self.size)
+            .map(|row| row.iter().map(|w| w.to_string()).collect::<Vec<String>>().join(","))
+            .collect();
+        write!(f, "{}", rows.join(";"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Kernel;
+
+    #[test]
+    fn kernels_sum_their_weights() {
+        let kernel = Kernel::from_rows(&[vec![0, 2, 0], vec![-1, 0, 1], vec![0, 0, 3]]).unwrap();
+        assert_eq!(kernel.to_string(), "0,2,0;-1,0,1;0,0,3");
+        assert_eq!((kernel.min_sum(), kernel.max_sum()), (-1, 6));
+        let offsets: Vec<_> = kernel.offsets().collect();
+        assert_eq!(offsets, [(-1, 0, 2), (0, -1, -1), (0, 1, 1), (1, 1, 3)]);
+        assert!(Kernel::from_rows(&[vec![1, 1], vec![1, 1]]).is_err());
+        assert!(Kernel::from_rows(&[vec![1, 1, 1], vec![1, 1], vec![1, 1, 1]]).is_err());
+        assert!(Kernel::from_rows(&vec![vec![1; 9]; 9]).is_err());
+        assert!(Kernel::from_rows(&[vec![101]]).is_err());
+    }
+}
@@ -4,20 +4,59 @@ use wasm_bindgen::JsCast;
 use std::cell::RefCell;
 use std::rc::Rc;
 
+mod bitwise;
+mod continuous;
+mod elementary;
+mod graph;
+mod hashlife;
+mod hensel;
+mod kernel;
+mod life3d;
+mod margolus;
+mod noise;
+mod obstacle;
+mod parallel;
+mod random;
 mod renderer;
 mod renderloop;
+mod region;
+mod rule;
+mod ruletable;
+mod schedule;
+mod simulation;
+mod sparse;
+mod tableuniverse;
+mod topology;
+mod turmite;
 mod universe;
 mod utils;
 
+use continuous::{Colormap, Continuous};
+use graph::{Graph, Tiling};
+use life3d::{Life3D, Projection};
+use noise::Noise;
+use obstacle::Obstacle;
+use region::Region;
 use renderer::Renderer;
 use renderloop::RenderLoop;
+use schedule::Schedule;
+use simulation::Simulation;
+use topology::Topology;
+use turmite::Turmite;
 use universe::Universe;
 
+// Largest k for the "step by 2^k" control, keeps HashLife coordinates well inside an i64
+const MAX_JUMP_POWER: u32 = 40;
+// Largest k for engines that step one tick at a time, keeps a jump from freezing the page
+const MAX_TICK_JUMP_POWER: u32 = 10;
+// Cells on each side of the clicked cell the region brush paints
+const REGION_BRUSH_RADIUS: isize = 2;
+
 #[wasm_bindgen(start)]
 pub fn start() -> Result<(), JsValue> {
     let window = window();
     let canvas = canvas();
-    let universe: Rc<RefCell<Universe>> = Rc::new(RefCell::new(Universe::new(100, 100)));
+    let universe: Rc<RefCell<Box<dyn Simulation>>> = Rc::new(RefCell::new(Box::new(Universe::new(100, 100))));
     let renderer: Rc<RefCell<Renderer>> = Rc::new(RefCell::new(Renderer::new(
         canvas.clone(),
         universe.clone(),
@@ -31,15 +70,18 @@ pub fn start() -> Result<(), JsValue> {
             let renderer = renderer.clone();
             Closure::wrap(Box::new(move || -> Result<(), JsValue> {
                 let row = row_input().value().parse::<usize>();
-                // let col = col_input().value().parse::<usize>();
-                if let Ok(r) = row /*&& let Ok(c) = col*/ {
-                    universe.borrow_mut().set_size(Some(r), Some(r)/*Some(c)*/);
+                let col = col_input().value().parse::<usize>();
+                if let (Ok(0), _) | (_, Ok(0)) = (&row, &col) {
+                    utils::log!("A universe needs at least one row and one column");
+                }
+                else if let (Ok(r), Ok(c)) = (&row, &col) {
+                    universe.borrow_mut().set_size(Some(*c), Some(*r));
                     renderer.borrow().draw();
                 }
                 else {
                     utils::log!("Could not parse row or col");
                     utils::log!("row: {:?}", row);
-                    // utils::log!("col: {:?}", col);
+                    utils::log!("col: {:?}", col);
                 }
                 Ok(())
             }))
@@ -48,6 +90,394 @@ pub fn start() -> Result<(), JsValue> {
         closure.forget();
     }
 
+    // topology select change listener
+    { 
+        let select = topology_select();
+        let closure: Closure<dyn Fn() -> _> = {
+            let universe = universe.clone();
+            Closure::wrap(Box::new(move || -> Result<(), JsValue> {
+                match Topology::parse(&topology_select().value()) {
+                    Ok(topology) => {
+                        let mut universe = universe.borrow_mut();
+                        match universe.as_any_mut().downcast_mut::<Universe>() {
+                            Some(grid) => grid.set_topology(topology),
+                            None => {
+                                utils::log!("Only the grid engine has edges");
+                            }
+                        }
+                    }
+                    Err(e) => {
+                        utils::log!("Could not parse topology: {}", e);
+                    }
+                }
+                Ok(())
+            }))
+        };
+        select.add_event_listener_with_callback("change", closure.as_ref().unchecked_ref())?;
+        closure.forget();
+    }
+
+    // colormap select change listener
+    { 
+        let select = colormap_select();
+        let closure: Closure<dyn Fn() -> _> = {
+            let universe = universe.clone();
+            let renderer = renderer.clone();
+            Closure::wrap(Box::new(move || -> Result<(), JsValue> {
+                match Colormap::parse(&colormap_select().value()) {
+                    Ok(colormap) => {
+                        let mut universe = universe.borrow_mut();
+                        match universe.as_any_mut().downcast_mut::<Continuous>() {
+                            Some(continuous) => continuous.set_colormap(colormap),
+                            None => {
+                                utils::log!("Only the continuous engine has colormaps");
+                            }
+                        }
+                    }
+                    Err(e) => {
+                        utils::log!("Could not parse colormap: {}", e);
+                    }
+                }
+                renderer.borrow().draw();
+                Ok(())
+            }))
+        };
+        select.add_event_listener_with_callback("change", closure.as_ref().unchecked_ref())?;
+        closure.forget();
+    }
+
+    // projection select and input change listener
+    { 
+        let select = projection_select();
+        let input = projection_input();
+        let closure: Closure<dyn Fn() -> _> = {
+            let universe = universe.clone();
+            let renderer = renderer.clone();
+            Closure::wrap(Box::new(move || -> Result<(), JsValue> {
+                match Projection::parse(&projection_select().value(), projection_input().value_as_number()) {
+                    Ok(projection) => {
+                        let mut universe = universe.borrow_mut();
+                        match universe.as_any_mut().downcast_mut::<Life3D>() {
+                            Some(life) => life.set_projection(projection),
+                            None => {
+                                utils::log!("Only the 3D engine has projections");
+                            }
+                        }
+                    }
+                    Err(e) => {
+                        utils::log!("Could not parse projection: {}", e);
+                    }
+                }
+                renderer.borrow().draw();
+                Ok(())
+            }))
+        };
+        select.add_event_listener_with_callback("change", closure.as_ref().unchecked_ref())?;
+        input.add_event_listener_with_callback("change", closure.as_ref().unchecked_ref())?;
+        closure.forget();
+    }
+
+    // tiling select change listener
+    { 
+        let select = tiling_select();
+        let closure: Closure<dyn Fn() -> _> = {
+            let universe = universe.clone();
+            let renderer = renderer.clone();
+            Closure::wrap(Box::new(move || -> Result<(), JsValue> {
+                match Tiling::parse(&tiling_select().value()) {
+                    Ok(tiling) => {
+                        let mut universe = universe.borrow_mut();
+                        match universe.as_any_mut().downcast_mut::<Graph>() {
+                            Some(graph) => graph.set_tiling(tiling),
+                            None => {
+                                utils::log!("Only the tiles engine has tilings");
+                            }
+                        }
+                    }
+                    Err(e) => {
+                        utils::log!("Could not parse tiling: {}", e);
+                    }
+                }
+                renderer.borrow().draw();
+                Ok(())
+            }))
+        };
+        select.add_event_listener_with_callback("change", closure.as_ref().unchecked_ref())?;
+        closure.forget();
+    }
+
+    // rule apply button listener
+    { 
+        let rule_apply_btn = rule_apply_btn();
+        let closure: Closure<dyn Fn() -> _> = {
+            let universe = universe.clone();
+            Closure::wrap(Box::new(move || -> Result<(), JsValue> {
+                let mut universe = universe.borrow_mut();
+                match universe.set_rule(&rule_input().value()) {
+                    Ok(()) => {
+                        rule_input().set_value(&universe.rule());
+                    }
+                    Err(e) => {
+                        utils::log!("Could not parse rule: {}", e);
+                    }
+                }
+                Ok(())
+            }))
+        };
+        rule_apply_btn.add_event_listener_with_callback("click", closure.as_ref().unchecked_ref())?;
+        closure.forget();
+    }
+
+    // noise apply button listener
+    { 
+        let noise_apply_btn = noise_apply_btn();
+        let closure: Closure<dyn Fn() -> _> = {
+            let universe = universe.clone();
+            Closure::wrap(Box::new(move || -> Result<(), JsValue> {
+                match Noise::parse(&noise_input().value()) {
+                    Ok(noise) => {
+                        let mut universe = universe.borrow_mut();
+                        match universe.as_any_mut().downcast_mut::<Universe>() {
+                            Some(grid) => {
+                                grid.set_noise(noise);
+                                noise_input().set_value(&grid.noise().to_string());
+                            }
+                            None => {
+                                utils::log!("Only the grid engine has noise");
+                            }
+                        }
+                    }
+                    Err(e) => {
+                        utils::log!("Could not parse noise: {}", e);
+                    }
+                }
+                Ok(())
+            }))
+        };
+        noise_apply_btn.add_event_listener_with_callback("click", closure.as_ref().unchecked_ref())?;
+        closure.forget();
+    }
+
+    // schedule apply button listener
+    { 
+        let schedule_apply_btn = schedule_apply_btn();
+        let closure: Closure<dyn Fn() -> _> = {
+            let universe = universe.clone();
+            Closure::wrap(Box::new(move || -> Result<(), JsValue> {
+                match Schedule::parse(&schedule_input().value()) {
+                    Ok(schedule) => {
+                        let mut universe = universe.borrow_mut();
+                        match universe.as_any_mut().downcast_mut::<Universe>() {
+                            Some(grid) => {
+                                grid.set_schedule(schedule);
+                                schedule_input().set_value(&grid.schedule().to_string());
+                            }
+                            None => {
+                                utils::log!("Only the grid engine has update schedules");
+                            }
+                        }
+                    }
+                    Err(e) => {
+                        utils::log!("Could not parse schedule: {}", e);
+                    }
+                }
+                Ok(())
+            }))
+        };
+        schedule_apply_btn.add_event_listener_with_callback("click", closure.as_ref().unchecked_ref())?;
+        closure.forget();
+    }
+
+    // region apply button listener, painting a rectangle with a rule
+    { 
+        let region_apply_btn = region_apply_btn();
+        let closure: Closure<dyn Fn() -> _> = {
+            let universe = universe.clone();
+            let renderer = renderer.clone();
+            Closure::wrap(Box::new(move || -> Result<(), JsValue> {
+                match Region::parse(&region_input().value()) {
+                    Ok(region) => {
+                        let mut universe = universe.borrow_mut();
+                        match universe.as_any_mut().downcast_mut::<Universe>() {
+                            Some(grid) => {
+                                let cells = region.cells(grid.width(), grid.height());
+                                if let Err(e) = grid.paint_rule(&cells, &region.rule) {
+                                    utils::log!("Could not paint region: {}", e);
+                                }
+                            }
+                            None => {
+                                utils::log!("Only the grid engine has rule regions");
+                            }
+                        }
+                    }
+                    Err(e) => {
+                        utils::log!("Could not parse region: {}", e);
+                    }
+                }
+                renderer.borrow().draw();
+                Ok(())
+            }))
+        };
+        region_apply_btn.add_event_listener_with_callback("click", closure.as_ref().unchecked_ref())?;
+        closure.forget();
+    }
+
+    // region clear button listener
+    { 
+        let region_clear_btn = region_clear_btn();
+        let closure: Closure<dyn Fn() -> _> = {
+            let universe = universe.clone();
+            let renderer = renderer.clone();
+            Closure::wrap(Box::new(move || -> Result<(), JsValue> {
+                match universe.borrow_mut().as_any_mut().downcast_mut::<Universe>() {
+                    Some(grid) => {
+                        grid.clear_regions();
+                    }
+                    None => {
+                        utils::log!("Only the grid engine has rule regions");
+                    }
+                }
+                renderer.borrow().draw();
+                Ok(())
+            }))
+        };
+        region_clear_btn.add_event_listener_with_callback("click", closure.as_ref().unchecked_ref())?;
+        closure.forget();
+    }
+
+    // turmite apply button listener, an empty turmite takes the ants away
+    { 
+        let turmite_apply_btn = turmite_apply_btn();
+        let closure: Closure<dyn Fn() -> _> = {
+            let universe = universe.clone();
+            let renderer = renderer.clone();
+            Closure::wrap(Box::new(move || -> Result<(), JsValue> {
+                let text = turmite_input().value();
+                let turmite = if text.trim().is_empty() { Ok(None) } else { Turmite::parse(&text).map(Some) };
+                match turmite {
+                    Ok(turmite) => {
+                        let mut universe = universe.borrow_mut();
+                        match universe.as_any_mut().downcast_mut::<Universe>() {
+                            Some(grid) => match grid.set_turmite(turmite) {
+                                Ok(()) => {
+                                    turmite_input().set_value(&grid.turmite().map_or_else(String::new, |turmite| turmite.to_string()));
+                                }
+                                Err(e) => {
+                                    utils::log!("Could not place the ants: {}", e);
+                                }
+                            },
+                            None => {
+                                utils::log!("Only the grid engine has ants");
+                            }
+                        }
+                    }
+                    Err(e) => {
+                        utils::log!("Could not parse turmite: {}", e);
+                    }
+                }
+                renderer.borrow().draw();
+                Ok(())
+            }))
+        };
+        turmite_apply_btn.add_event_listener_with_callback("click", closure.as_ref().unchecked_ref())?;
+        closure.forget();
+    }
+
+    // rule file input listener
+    { 
+        let input = rule_file_input();
+        let closure: Closure<dyn Fn() -> _> = {
+            let universe = universe.clone();
+            let renderer = renderer.clone();
+            Closure::wrap(Box::new(move || -> Result<(), JsValue> {
+                let file = match rule_file_input().files().and_then(|files| files.get(0)) {
+                    Some(file) => file,
+                    None => return Ok(()),
+                };
+                let reader = web_sys::FileReader::new()?;
+                let onload: Closure<dyn Fn() -> _> = {
+                    let universe = universe.clone();
+                    let renderer = renderer.clone();
+                    let reader = reader.clone();
+                    Closure::wrap(Box::new(move || -> Result<(), JsValue> {
+                        let text = reader.result()?.as_string().unwrap_or_default();
+                        // Rule tables run on their own engine and weighted rules on the grid
+                        let weighted = text.trim_start().get(..8).is_some_and(|name| name.eq_ignore_ascii_case("weighted"));
+                        let engine = if weighted { "grid" } else { "table" };
+                        if engine_select().value() != engine {
+                            engine_select().set_value(engine);
+                            engine_select().dispatch_event(&web_sys::Event::new("change")?)?;
+                        }
+                        {
+                            let mut universe = universe.borrow_mut();
+                            match universe.set_rule(&text) {
+                                Ok(()) => rule_input().set_value(&universe.rule()),
+                                Err(e) => {
+                                    utils::log!("Could not load rule: {}", e);
+                                }
+                            }
+                        }
+                        renderer.borrow().draw();
+                        Ok(())
+                    }))
+                };
+                reader.set_onload(Some(onload.as_ref().unchecked_ref()));
+                onload.forget();
+                reader.read_as_text(&file)?;
+                Ok(())
+            }))
+        };
+        input.add_event_listener_with_callback("change", closure.as_ref().unchecked_ref())?;
+        closure.forget();
+    }
+
+    // engine select change listener
+    { 
+        let select = engine_select();
+        let closure: Closure<dyn Fn() -> _> = {
+            let universe = universe.clone();
+            let renderer = renderer.clone();
+            Closure::wrap(Box::new(move || -> Result<(), JsValue> {
+                {
+                    let mut universe = universe.borrow_mut();
+                    match simulation::create(&engine_select().value(), universe.width(), universe.height()) {
+                        Ok(mut engine) => {
+                            // Carry the rule and the live cells over to the new engine
+                            if let Err(e) = engine.set_rule(&universe.rule()) {
+                                utils::log!("Could not keep rule: {}", e);
+                            }
+                            let cells: Vec<(usize, usize)> = universe.get_live_cells().iter()
+                                .filter(|(_, _, state)| *state == 1.0)
+                                .map(|(row, col, _)| (*row as usize, *col as usize))
+                                .collect();
+                            engine.set_cells(&cells);
+                            rule_input().set_value(&engine.rule());
+                            topology_select().set_value("torus");
+                            // The new engine starts without noise, ants or regions and updates in sync,
+                            // with the default colors, 3D view and tiling
+                            noise_input().set_value("");
+                            schedule_input().set_value("sync");
+                            turmite_input().set_value("");
+                            region_input().set_value("");
+                            colormap_select().set_value("viridis");
+                            projection_select().set_value("isometric");
+                            projection_input().set_value("30");
+                            tiling_select().set_value("penrose");
+                            *universe = engine;
+                        }
+                        Err(e) => {
+                            utils::log!("Could not create engine: {}", e);
+                        }
+                    }
+                }
+                renderer.borrow().draw();
+                Ok(())
+            }))
+        };
+        select.add_event_listener_with_callback("change", closure.as_ref().unchecked_ref())?;
+        closure.forget();
+    }
+
     // view reset apply button listener
     { 
         let view_apply_btn = view_apply_btn();
@@ -127,7 +557,6 @@ pub fn start() -> Result<(), JsValue> {
     // Mouse mouseup handler on canvas
     {
         let closure: Closure<dyn Fn(_)> = {
-            let canvas = canvas.clone();
             let universe = universe.clone();
             let renderer = renderer.clone();
             Closure::wrap(Box::new(move |event: web_sys::MouseEvent| {
@@ -135,28 +564,20 @@ pub fn start() -> Result<(), JsValue> {
                     let client_x = event.client_x();
                     let client_y = event.client_y();
                     renderer.borrow_mut().end_position(client_x, client_y);
+                    let (y, x) = renderer.borrow().get_cell_position(client_x, client_y);
 
                     let mut universe = universe.borrow_mut();
                     let w = universe.width() as isize;
                     let h = universe.height() as isize;
-                    let view_offset = renderer.borrow().get_view_position();
-                    let view_scale = renderer.borrow().get_view_scale();
-                    let bounding_rect = (canvas.as_ref() as &web_sys::Element).get_bounding_client_rect();
-                    let canvas_small_side = canvas.width().min(canvas.height()) as f64 * view_scale;
-                    let viewport_offset_x = (canvas.width() as f64 - canvas_small_side) / 2.0 - view_offset.0 as f64;
-                    let viewport_offset_y = (canvas.height() as f64 - canvas_small_side) / 2.0 - view_offset.1 as f64;
-                    let rect_small_side = bounding_rect.width().min(bounding_rect.height());
-                    let scale_x = w as f64 / rect_small_side / view_scale;
-                    let scale_y = h as f64 / rect_small_side / view_scale;
-                    let x = ((client_x as f64 - bounding_rect.left() - viewport_offset_x) * scale_x) as isize;
-                    let y = ((client_y as f64 - bounding_rect.top() - viewport_offset_y) * scale_y) as isize;
-
                     let row = in_bounds(y, h);
                     let col = in_bounds(x, w);
-                    if event.ctrl_key() {
+                    if let Err(e) = universe.editable() {
+                        utils::log!("Could not edit cells: {}", e);
+                    }
+                    else if event.ctrl_key() {
                         if event.alt_key() {
                             let mut cells = Vec::new();
-                            for r in 0..w {
+                            for r in 0..h {
                                 cells.push((r as usize, col));
                             }
                             universe.toggle_cells(&cells);
@@ -169,9 +590,45 @@ pub fn start() -> Result<(), JsValue> {
                                                        (in_bounds(y + 1, h), in_bounds(x - 1, w))]);
                         }
                     }
-                    else {
+                    else if brush_select().value() == "cells" {
                         universe.toggle_cell(row, col);
                     }
+                    else if brush_select().value() == "region" {
+                        // The region brush paints a square around the cell with the region's rule
+                        let mut cells = Vec::new();
+                        for r in y - REGION_BRUSH_RADIUS..=y + REGION_BRUSH_RADIUS {
+                            for c in x - REGION_BRUSH_RADIUS..=x + REGION_BRUSH_RADIUS {
+                                cells.push((in_bounds(r, h), in_bounds(c, w)));
+                            }
+                        }
+                        let painted = Region::parse(&region_input().value()).and_then(|region| {
+                            match universe.as_any_mut().downcast_mut::<Universe>() {
+                                Some(grid) => grid.paint_rule(&cells, &region.rule),
+                                None => Err(String::from("only the grid engine has rule regions")),
+                            }
+                        });
+                        if let Err(e) = painted {
+                            utils::log!("Could not paint region: {}", e);
+                        }
+                    }
+                    else {
+                        let period = emitter_period_input().value().parse::<u64>().unwrap_or(0);
+                        match Obstacle::parse(&brush_select().value(), period) {
+                            Ok(obstacle) => {
+                                match universe.as_any_mut().downcast_mut::<Universe>() {
+                                    Some(grid) => {
+                                        grid.toggle_obstacle(row, col, obstacle);
+                                    }
+                                    None => {
+                                        utils::log!("Only the grid engine has obstacles");
+                                    }
+                                }
+                            }
+                            Err(e) => {
+                                utils::log!("Could not place obstacle: {}", e);
+                            }
+                        }
+                    }
                 }
                 renderer.borrow().draw();
             }))
@@ -212,14 +669,16 @@ pub fn start() -> Result<(), JsValue> {
 
     let play_pause_btn = play_pause_btn();
     let fps_label = fps_label();
+    let generation_label = generation_label();
     
     // Render loop handling
     let render_loop: Rc<RefCell<RenderLoop>> = Rc::new(RefCell::new(RenderLoop::new(
         window.clone(),
         play_pause_btn.clone(),
         fps_label.clone(),
+        generation_label.clone(),
         universe.clone(),
-        renderer,
+        renderer.clone(),
     )));
     render_loop.borrow_mut().closure = Some({
         let render_loop = render_loop.clone();
@@ -242,6 +701,54 @@ pub fn start() -> Result<(), JsValue> {
         closure.forget();
     }
 
+    // jump button listener
+    { 
+        let closure: Closure<dyn Fn() -> _> = {
+            let universe = universe.clone();
+            let renderer = renderer.clone();
+            let render_loop = render_loop.clone();
+            Closure::wrap(Box::new(move || -> Result<(), JsValue> {
+                let power = jump_input().value().parse::<u32>();
+                let max_power = if universe.borrow().can_jump() { MAX_JUMP_POWER } else { MAX_TICK_JUMP_POWER };
+                match power {
+                    Ok(k) if k <= max_power => {
+                        universe.borrow_mut().step(1 << k);
+                        renderer.borrow().draw();
+                        render_loop.borrow().update_generation_label();
+                    }
+                    Ok(k) if k <= MAX_JUMP_POWER => {
+                        utils::log!("Only HashLife can step by 2^{}, this engine goes up to 2^{}", k, max_power);
+                    }
+                    _ => {
+                        utils::log!("Could not parse jump power: {:?}", power);
+                    }
+                }
+                Ok(())
+            }))
+        };
+        jump_apply_btn().add_event_listener_with_callback("click", closure.as_ref().unchecked_ref())?;
+        closure.forget();
+    }
+
+    // step back button listener
+    { 
+        let closure: Closure<dyn Fn() -> _> = {
+            let universe = universe.clone();
+            let renderer = renderer.clone();
+            let render_loop = render_loop.clone();
+            Closure::wrap(Box::new(move || -> Result<(), JsValue> {
+                if let Err(e) = universe.borrow_mut().step_back() {
+                    utils::log!("Could not step back: {}", e);
+                }
+                renderer.borrow().draw();
+                render_loop.borrow().update_generation_label();
+                Ok(())
+            }))
+        };
+        step_back_btn().add_event_listener_with_callback("click", closure.as_ref().unchecked_ref())?;
+        closure.forget();
+    }
+
     // fps input listener
     {
         let fps_input = fps_input();
@@ -326,16 +833,116 @@ fn row_input() -> web_sys::HtmlInputElement {
     btn.dyn_into::<web_sys::HtmlInputElement>().expect("dyn_into for row input failed")
 }
 
-// fn col_input() -> web_sys::HtmlInputElement {
-//     let btn = document().get_element_by_id("col-input").expect("document should have a col input");
-//     btn.dyn_into::<web_sys::HtmlInputElement>().expect("dyn_into for col input failed")
-// }
+fn col_input() -> web_sys::HtmlInputElement {
+    let btn = document().get_element_by_id("col-input").expect("document should have a col input");
+    btn.dyn_into::<web_sys::HtmlInputElement>().expect("dyn_into for col input failed")
+}
 
 fn universe_apply_btn() -> web_sys::HtmlButtonElement {
     let btn = document().get_element_by_id("universe-apply").expect("document should have a universe-apply button");
     btn.dyn_into::<web_sys::HtmlButtonElement>().expect("dyn_into for universe-apply button failed")
 }
 
+fn topology_select() -> web_sys::HtmlSelectElement {
+    let select = document().get_element_by_id("topology-select").expect("document should have a topology select");
+    select.dyn_into::<web_sys::HtmlSelectElement>().expect("dyn_into for topology select failed")
+}
+
+fn engine_select() -> web_sys::HtmlSelectElement {
+    let select = document().get_element_by_id("engine-select").expect("document should have an engine select");
+    select.dyn_into::<web_sys::HtmlSelectElement>().expect("dyn_into for engine select failed")
+}
+
+fn colormap_select() -> web_sys::HtmlSelectElement {
+    let select = document().get_element_by_id("colormap-select").expect("document should have a colormap select");
+    select.dyn_into::<web_sys::HtmlSelectElement>().expect("dyn_into for colormap select failed")
+}
+
+fn projection_select() -> web_sys::HtmlSelectElement {
+    let select = document().get_element_by_id("projection-select").expect("document should have a projection select");
+    select.dyn_into::<web_sys::HtmlSelectElement>().expect("dyn_into for projection select failed")
+}
+
+fn projection_input() -> web_sys::HtmlInputElement {
+    let input = document().get_element_by_id("projection-input").expect("document should have a projection input");
+    input.dyn_into::<web_sys::HtmlInputElement>().expect("dyn_into for projection input failed")
+}
+
+fn tiling_select() -> web_sys::HtmlSelectElement {
+    let select = document().get_element_by_id("tiling-select").expect("document should have a tiling select");
+    select.dyn_into::<web_sys::HtmlSelectElement>().expect("dyn_into for tiling select failed")
+}
+
+fn rule_file_input() -> web_sys::HtmlInputElement {
+    let input = document().get_element_by_id("rule-file").expect("document should have a rule-file input");
+    input.dyn_into::<web_sys::HtmlInputElement>().expect("dyn_into for rule-file input failed")
+}
+
+fn rule_input() -> web_sys::HtmlInputElement {
+    let btn = document().get_element_by_id("rule-input").expect("document should have a rule input");
+    btn.dyn_into::<web_sys::HtmlInputElement>().expect("dyn_into for rule input failed")
+}
+
+fn rule_apply_btn() -> web_sys::HtmlButtonElement {
+    let btn = document().get_element_by_id("rule-apply").expect("document should have a rule-apply button");
+    btn.dyn_into::<web_sys::HtmlButtonElement>().expect("dyn_into for rule-apply button failed")
+}
+
+fn noise_input() -> web_sys::HtmlInputElement {
+    let input = document().get_element_by_id("noise-input").expect("document should have a noise input");
+    input.dyn_into::<web_sys::HtmlInputElement>().expect("dyn_into for noise input failed")
+}
+
+fn noise_apply_btn() -> web_sys::HtmlButtonElement {
+    let btn = document().get_element_by_id("noise-apply").expect("document should have a noise-apply button");
+    btn.dyn_into::<web_sys::HtmlButtonElement>().expect("dyn_into for noise-apply button failed")
+}
+
+fn schedule_input() -> web_sys::HtmlInputElement {
+    let input = document().get_element_by_id("schedule-input").expect("document should have a schedule input");
+    input.dyn_into::<web_sys::HtmlInputElement>().expect("dyn_into for schedule input failed")
+}
+
+fn schedule_apply_btn() -> web_sys::HtmlButtonElement {
+    let btn = document().get_element_by_id("schedule-apply").expect("document should have a schedule-apply button");
+    btn.dyn_into::<web_sys::HtmlButtonElement>().expect("dyn_into for schedule-apply button failed")
+}
+
+fn brush_select() -> web_sys::HtmlSelectElement {
+    let select = document().get_element_by_id("brush-select").expect("document should have a brush select");
+    select.dyn_into::<web_sys::HtmlSelectElement>().expect("dyn_into for brush select failed")
+}
+
+fn emitter_period_input() -> web_sys::HtmlInputElement {
+    let input = document().get_element_by_id("emitter-period-input").expect("document should have an emitter period input");
+    input.dyn_into::<web_sys::HtmlInputElement>().expect("dyn_into for emitter period input failed")
+}
+
+fn region_input() -> web_sys::HtmlInputElement {
+    let input = document().get_element_by_id("region-input").expect("document should have a region input");
+    input.dyn_into::<web_sys::HtmlInputElement>().expect("dyn_into for region input failed")
+}
+
+fn region_apply_btn() -> web_sys::HtmlButtonElement {
+    let btn = document().get_element_by_id("region-apply").expect("document should have a region-apply button");
+    btn.dyn_into::<web_sys::HtmlButtonElement>().expect("dyn_into for region-apply button failed")
+}
+
+fn region_clear_btn() -> web_sys::HtmlButtonElement {
+    let btn = document().get_element_by_id("region-clear").expect("document should have a region-clear button");
+    btn.dyn_into::<web_sys::HtmlButtonElement>().expect("dyn_into for region-clear button failed")
+}
+
+fn turmite_input() -> web_sys::HtmlInputElement {
+    let input = document().get_element_by_id("turmite-input").expect("document should have a turmite input");
+    input.dyn_into::<web_sys::HtmlInputElement>().expect("dyn_into for turmite input failed")
+}
+
+fn turmite_apply_btn() -> web_sys::HtmlButtonElement {
+    let btn = document().get_element_by_id("turmite-apply").expect("document should have a turmite-apply button");
+    btn.dyn_into::<web_sys::HtmlButtonElement>().expect("dyn_into for turmite-apply button failed")
+}
+
 fn view_scale_input() -> web_sys::HtmlInputElement {
     let btn = document().get_element_by_id("scale-input").expect("document should have a scale input");
     btn.dyn_into::<web_sys::HtmlInputElement>().expect("dyn_into for scale input failed")
@@ -361,6 +968,26 @@ fn fps_label() -> web_sys::HtmlDivElement {
     btn.dyn_into::<web_sys::HtmlDivElement>().expect("dyn_into for fps-label div failed")
 }
 
+fn generation_label() -> web_sys::HtmlDivElement {
+    let btn = document().get_element_by_id("generation-label").expect("document should have a generation-label div");
+    btn.dyn_into::<web_sys::HtmlDivElement>().expect("dyn_into for generation-label div failed")
+}
+
+fn jump_input() -> web_sys::HtmlInputElement {
+    let btn = document().get_element_by_id("jump-input").expect("document should have a jump input");
+    btn.dyn_into::<web_sys::HtmlInputElement>().expect("dyn_into for jump input failed")
+}
+
+fn jump_apply_btn() -> web_sys::HtmlButtonElement {
+    let btn = document().get_element_by_id("jump-apply").expect("document should have a jump-apply button");
+    btn.dyn_into::<web_sys::HtmlButtonElement>().expect("dyn_into for jump-apply button failed")
+}
+
+fn step_back_btn() -> web_sys::HtmlButtonElement {
+    let btn = document().get_element_by_id("step-back").expect("document should have a step-back button");
+    btn.dyn_into::<web_sys::HtmlButtonElement>().expect("dyn_into for step-back button failed")
+}
+
 fn tpf_input() -> web_sys::HtmlInputElement {
     let btn = document().get_element_by_id("tpf-range").expect("document should have a tpf-range input");
     btn.dyn_into::<web_sys::HtmlInputElement>().expect("dyn_into for tpf-range input failed")
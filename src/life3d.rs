@@ -0,0 +1,446 @@
+extern crate fixedbitset;
+
+use std::any::Any;
+use std::f32::consts::PI;
+use std::fmt;
+
+use fixedbitset::FixedBitSet;
+
+use crate::parallel;
+use crate::random;
+use crate::simulation::Simulation;
+
+const MAX_NEIGHBORS: usize = 26;
+// Layers of the universe at most, the depth follows the smaller of width and height
+const MAX_DEPTH: usize = 64;
+// Shades of live cells in the isometric projection, from the farthest to the nearest
+const SHADES: u8 = 16;
+// Side of the cube of random cells a new universe starts with
+const SOUP_SIZE: usize = 10;
+
+/// A three dimensional Life-like rule such as "B6/S567", counting the 26 cells
+/// around a cell. Counts above 9 are written with commas and ranges, e.g. "B6/S5..7,14".
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Rule3D {
+    birth: FixedBitSet,
+    survival: FixedBitSet,
+}
+
+impl Rule3D {
+
+    pub fn parse(rule: &str) -> Result<Rule3D, String> {
+        let rule = rule.trim();
+        let (birth, survival) = match rule.split_once('/') {
+            Some((birth, survival)) => (birth.trim(), survival.trim()),
+            None => return Err(format!("rule '{}' should have a B and an S part separated by '/'", rule)),
+        };
+        let birth = birth.strip_prefix(['B', 'b']).ok_or_else(|| format!("'{}' should start with B", birth))?;
+        let survival = survival.strip_prefix(['S', 's']).ok_or_else(|| format!("'{}' should start with S", survival))?;
+        Ok(Rule3D {
+            birth: Rule3D::parse_counts(birth)?,
+            survival: Rule3D::parse_counts(survival)?,
+        })
+    }
+
+    /// Parses single digit counts such as "567", or comma separated counts and ranges such as "5..7,14".
+    fn parse_counts(counts: &str) -> Result<FixedBitSet, String> {
+        let mut set = FixedBitSet::with_capacity(MAX_NEIGHBORS + 1);
+        let items: Vec<&str> = if counts.contains([',', '.']) {
+            counts.split(',').map(|item| item.trim()).collect()
+        } else {
+            counts.matches(|_: char| true).collect()
+        };
+        for item in items {
+            let bounds = match item.split_once("..") {
+                Some((min, max)) => (min.parse::<usize>(), max.parse::<usize>()),
+                None => (item.parse::<usize>(), item.parse::<usize>()),
+            };
+            match bounds {
+                (Ok(min), Ok(max)) if min <= max && max <= MAX_NEIGHBORS => set.insert_range(min..max + 1),
+                _ => return Err(format!("'{}' is not a count or range of counts up to {}", item, MAX_NEIGHBORS)),
+            }
+        }
+        Ok(set)
+    }
+
+    fn fmt_counts(f: &mut fmt::Formatter, counts: &FixedBitSet) -> fmt::Result {
+        if counts.ones().all(|n| n < 10) {
+            return counts.ones().try_for_each(|n| write!(f, "{}", n));
+        }
+        let counts: Vec<String> = counts.ones().map(|n| n.to_string()).collect();
+        write!(f, "{}", counts.join(","))
+    }
+
+    pub fn next_state(&self, alive: bool, live_neighbors: usize) -> bool {
+        if alive {
+            self.survival.contains(live_neighbors)
+        } else {
+            self.birth.contains(live_neighbors)
+        }
+    }
+}
+
+impl fmt::Display for Rule3D {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "B")?;
+        Rule3D::fmt_counts(f, &self.birth)?;
+        write!(f, "/S")?;
+        Rule3D::fmt_counts(f, &self.survival)
+    }
+}
+
+/// How the layers of a three dimensional universe are shown on the flat canvas.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Projection {
+    /// One layer seen straight on.
+    Slice(usize),
+    /// The whole universe seen from above at an angle, turned by the given degrees
+    /// around the vertical axis through its layers.
+    Isometric(f32),
+}
+
+impl Projection {
+
+    /// Parses the name of a projection as used by the settings bar, "slice" or
+    /// "isometric", with the layer or the angle it needs.
+    pub fn parse(name: &str, amount: f64) -> Result<Projection, String> {
+        match name.trim().to_ascii_lowercase().as_str() {
+            "slice" if amount >= 0.0 => Ok(Projection::Slice(amount as usize)),
+            "slice" => Err(format!("layer {} should be 0 or more", amount)),
+            "isometric" => Ok(Projection::Isometric(amount as f32)),
+            _ => Err(format!("'{}' is not a projection", name)),
+        }
+    }
+}
+
+/// A wrapping three dimensional universe of `depth` layers of `width` by `height` cells.
+///
+/// The live cells handed to the renderer are a flat view of the layers, either
+/// one layer or an isometric projection of the whole universe. The projection
+/// sorts the cells from back to front and shades them by distance, so the
+/// renderer draws it like any other universe.
+pub struct Life3D {
+    width: usize,
+    height: usize,
+    depth: usize,
+    generation: u64,
+    rule: Rule3D,
+    projection: Projection,
+    cells: FixedBitSet,
+    live_cells: Vec<(f32,f32,f32)>
+}
+
+impl Life3D {
+
+    /// Creates a universe running B6/S567 from a cube of random cells in the middle, seen from above.
+    pub fn new(width: usize, height: usize) -> Life3D {
+        let mut life = Life3D {
+            width,
+            height,
+            depth: Life3D::depth_for(width, height),
+            generation: 0,
+            rule: Rule3D::parse("B6/S567").unwrap(),
+            projection: Projection::Isometric(30.0),
+            cells: FixedBitSet::new(),
+            live_cells: Vec::new()
+        };
+        life.reset_cells();
+        life
+    }
+
+    pub fn set_projection(&mut self, projection: Projection) {
+        self.projection = match projection {
+            Projection::Slice(layer) => Projection::Slice(layer.min(self.depth - 1)),
+            isometric => isometric,
+        };
+        self.refresh_live_cell_list();
+    }
+
+    fn depth_for(width: usize, height: usize) -> usize {
+        width.min(height).clamp(1, MAX_DEPTH)
+    }
+
+    fn get_index(&self, layer: usize, row: usize, col: usize) -> usize {
+        (layer * self.height + row) * self.width + col
+    }
+
+    /// Gets the layer placed patterns go to, the shown slice or the middle layer.
+    fn edit_layer(&self) -> usize {
+        match self.projection {
+            Projection::Slice(layer) => layer,
+            Projection::Isometric(_) => self.depth / 2,
+        }
+    }
+
+    fn toggle_in_layer(&mut self, layer: usize, cells: &[(usize, usize)]) {
+        for (row, col) in cells {
+            let idx = self.get_index(layer, *row, *col);
+            self.cells.toggle(idx);
+        }
+        self.refresh_live_cell_list();
+    }
+
+    fn reset_cells(&mut self) {
+        self.cells = FixedBitSet::with_capacity(self.width * self.height * self.depth);
+        let center = [self.depth / 2, self.height / 2, self.width / 2];
+        let start = center.map(|c| c.saturating_sub(SOUP_SIZE / 2));
+        for layer in start[0]..(start[0] + SOUP_SIZE).min(self.depth) {
+            for row in start[1]..(start[1] + SOUP_SIZE).min(self.height) {
+                for col in start[2]..(start[2] + SOUP_SIZE).min(self.width) {
+                    let idx = self.get_index(layer, row, col);
+                    if random::unit(3, 0, idx, 0) < 0.375 {
+                        self.cells.insert(idx);
+                    }
+                }
+            }
+        }
+        self.projection = match self.projection {
+            Projection::Slice(_) => Projection::Slice(self.depth / 2),
+            isometric => isometric,
+        };
+        self.refresh_live_cell_list();
+    }
+
+    fn alive_at(&self, layer: isize, row: isize, col: isize) -> bool {
+        let layer = layer.rem_euclid(self.depth as isize) as usize;
+        let row = row.rem_euclid(self.height as isize) as usize;
+        let col = col.rem_euclid(self.width as isize) as usize;
+        self.cells[self.get_index(layer, row, col)]
+    }
+
+    /// Gets the next states of a band of layers, reading only the current generation.
+    fn next_band_states(&self, layers: std::ops::Range<usize>) -> Vec<bool> {
+        let mut states = Vec::with_capacity(layers.len() * self.width * self.height);
+        for layer in layers {
+            for row in 0..self.height {
+                for col in 0..self.width {
+                    let (l, r, c) = (layer as isize, row as isize, col as isize);
+                    let mut count = 0;
+                    for dl in -1..=1 {
+                        for dr in -1..=1 {
+                            for dc in -1..=1 {
+                                if (dl, dr, dc) != (0, 0, 0) && self.alive_at(l + dl, r + dr, c + dc) {
+                                    count += 1;
+                                }
+                            }
+                        }
+                    }
+                    states.push(self.rule.next_state(self.cells[self.get_index(layer, row, col)], count));
+                }
+            }
+        }
+        states
+    }
+
+    /// Gets whether a live cell has a dead cell on at least one of its six faces,
+    /// cells buried inside are left out of the projection.
+    fn is_exposed(&self, layer: usize, row: usize, col: usize) -> bool {
+        let (l, r, c) = (layer as isize, row as isize, col as isize);
+        [(-1, 0, 0), (1, 0, 0), (0, -1, 0), (0, 1, 0), (0, 0, -1), (0, 0, 1)]
+            .iter()
+            .any(|(dl, dr, dc)| !self.alive_at(l + dl, r + dr, c + dc))
+    }
+
+    fn refresh_live_cell_list(&mut self) {
+        self.live_cells.clear();
+        match self.projection {
+            Projection::Slice(layer) => {
+                let (start, width) = (self.get_index(layer, 0, 0), self.width);
+                let end = start + width * self.height;
+                for idx in self.cells.ones().skip_while(|idx| *idx < start).take_while(|idx| *idx < end) {
+                    let (row, col) = ((idx - start) / width, (idx - start) % width);
+                    self.live_cells.push((row as f32, col as f32, SHADES as f32));
+                }
+            }
+            Projection::Isometric(angle) => {
+                let (sin, cos) = (angle * PI / 180.0).sin_cos();
+                // Looking down at the classic isometric angle, about 35 degrees
+                let (tilt_sin, tilt_cos) = (1.0 / 3f32.sqrt(), (2.0 / 3f32).sqrt());
+                let center = [self.depth, self.height, self.width].map(|size| (size as f32 - 1.0) / 2.0);
+                let radius = (center[0] * center[0] + center[1] * center[1] + center[2] * center[2]).sqrt().max(1.0);
+                let scale = (self.width.min(self.height) as f32 - 1.0) / 2.0 / radius;
+                let mut projected = Vec::new();
+                for idx in self.cells.ones() {
+                    let layer = idx / (self.width * self.height);
+                    let (row, col) = (idx / self.width % self.height, idx % self.width);
+                    if !self.is_exposed(layer, row, col) {
+                        continue;
+                    }
+                    let up = layer as f32 - center[0];
+                    let (y, x) = (row as f32 - center[1], col as f32 - center[2]);
+                    // Turn around the vertical axis, then tilt the top towards the viewer
+                    let across = x * cos - y * sin;
+                    let toward = x * sin + y * cos;
+                    let screen_row = toward * tilt_sin - up * tilt_cos;
+                    let nearness = toward * tilt_cos + up * tilt_sin;
+                    projected.push((nearness, screen_row, across));
+                }
+                projected.sort_by(|a, b| a.0.total_cmp(&b.0));
+                for (nearness, screen_row, across) in projected {
+                    let shade = ((nearness / radius + 1.0) / 2.0 * (SHADES - 1) as f32).round().clamp(0.0, (SHADES - 1) as f32);
+                    self.live_cells.push((
+                        (self.height as f32 - 1.0) / 2.0 + screen_row * scale,
+                        (self.width as f32 - 1.0) / 2.0 + across * scale,
+                        1.0 + shade,
+                    ));
+                }
+            }
+        }
+    }
+}
+
+impl Simulation for Life3D {
+    fn width(&self) -> usize {
+        self.width
+    }
+
+    fn height(&self) -> usize {
+        self.height
+    }
+
+    fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    fn tick(&mut self) {
+        self.generation += 1;
+        let bands = parallel::bands(self.depth, parallel::MIN_BAND_CELLS / (self.width * self.height));
+        let life = &*self;
+        let states = parallel::map_bands(bands, |layers| life.next_band_states(layers));
+        for (idx, alive) in states.into_iter().flatten().enumerate() {
+            self.cells.set(idx, alive);
+        }
+        self.refresh_live_cell_list();
+    }
+
+    fn rule(&self) -> String {
+        self.rule.to_string()
+    }
+
+    fn set_rule(&mut self, rule: &str) -> Result<(), String> {
+        self.rule = Rule3D::parse(rule)?;
+        Ok(())
+    }
+
+    fn state_count(&self) -> u8 {
+        SHADES + 1
+    }
+
+    /// Live cells go from dark blue far away to pale blue up close.
+    fn palette(&self) -> Vec<[f32; 3]> {
+        let (far, near) = ([0.15, 0.25, 0.5], [0.75, 0.9, 1.0]);
+        let mut palette = vec![[0.3, 0.3, 0.3]];
+        palette.extend((0..SHADES).map(|shade| {
+            let t = shade as f32 / (SHADES - 1) as f32;
+            [0, 1, 2].map(|c| far[c] + (near[c] - far[c]) * t)
+        }));
+        palette
+    }
+
+    fn get_live_cells(&self) -> &[(f32,f32,f32)] {
+        &self.live_cells
+    }
+
+    fn toggle_cell(&mut self, row: usize, col: usize) {
+        self.toggle_cells(&[(row, col)]);
+    }
+
+    /// Toggles cells of the shown slice. A screen position in the isometric
+    /// projection is no single cell, so nothing is toggled there.
+    fn toggle_cells(&mut self, cells: &[(usize, usize)]) {
+        if let Projection::Slice(layer) = self.projection {
+            self.toggle_in_layer(layer, cells);
+        }
+    }
+
+    /// Clears the universe and places the cells in the shown slice, or the middle layer.
+    fn set_cells(&mut self, cells: &[(usize, usize)]) {
+        self.cells.clear();
+        self.toggle_in_layer(self.edit_layer(), cells);
+    }
+
+    fn editable(&self) -> Result<(), String> {
+        match self.projection {
+            Projection::Slice(_) => Ok(()),
+            Projection::Isometric(_) => Err(String::from("The isometric view has no single cell under the pointer, switch to a slice to edit")),
+        }
+    }
+
+    fn set_size(&mut self, width: Option<usize>, height: Option<usize>) {
+        if let Some(w) = width {
+            self.width = w;
+        }
+        if let Some(h) = height {
+            self.height = h;
+        }
+        if width.is_some() || height.is_some() {
+            self.generation = 0;
+            self.depth = Life3D::depth_for(self.width, self.height);
+            self.reset_cells();
+        }
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Life3D, Projection, Rule3D, SHADES};
+    use crate::simulation::Simulation;
+
+    #[test]
+    fn parses_rules() {
+        for rule in ["B6/S567", "B5/S45", "B/S", "B4,14/S5,6,7,26"] {
+            assert_eq!(Rule3D::parse(rule).unwrap().to_string(), rule);
+        }
+        assert_eq!(Rule3D::parse("b6/s5..7").unwrap(), Rule3D::parse("B6/S567").unwrap());
+        assert!(Rule3D::parse("B6,27/S5").is_err());
+        assert!(Rule3D::parse("B6").is_err());
+        assert!(Rule3D::parse("S5/B6").is_err());
+    }
+
+    #[test]
+    fn single_cell_gives_birth_to_its_26_neighbors() {
+        let mut life = Life3D::new(12, 12);
+        life.set_rule("B1/S").unwrap();
+        life.set_projection(Projection::Slice(3));
+        life.set_cells(&[(6, 6)]);
+        life.tick();
+        assert_eq!(life.cells.count_ones(..), 26);
+        // The middle layer of the cube around the old cell is a ring of eight
+        assert_eq!(life.get_live_cells().len(), 8);
+        life.set_projection(Projection::Slice(2));
+        assert_eq!(life.get_live_cells().len(), 9);
+    }
+
+    #[test]
+    fn isometric_projection_stays_in_the_universe() {
+        let mut life = Life3D::new(40, 30);
+        for angle in [0.0, 30.0, 45.0, 200.0] {
+            life.set_projection(Projection::Isometric(angle));
+            let cells = life.get_live_cells();
+            assert!(!cells.is_empty());
+            assert!(cells.iter().all(|(row, col, state)| {
+                (0.0..=29.0).contains(row) && (0.0..=39.0).contains(col) && (1.0..=SHADES as f32).contains(state)
+            }));
+            // Drawn from back to front, so nearer cells are never darker than the ones before
+            assert!(cells.windows(2).all(|pair| pair[0].2 <= pair[1].2));
+        }
+    }
+
+    #[test]
+    fn clicks_only_edit_the_slice_view() {
+        let mut life = Life3D::new(12, 12);
+        life.set_projection(Projection::Isometric(30.0));
+        life.set_cells(&[(6, 6)]);
+        assert!(life.editable().is_err());
+        life.toggle_cell(2, 2);
+        assert_eq!(life.cells.count_ones(..), 1);
+        life.set_projection(Projection::Slice(life.depth / 2));
+        assert!(life.editable().is_ok());
+        life.toggle_cell(6, 6);
+        assert_eq!(life.cells.count_ones(..), 0);
+    }
+}
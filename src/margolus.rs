@@ -0,0 +1,266 @@
+extern crate fixedbitset;
+
+use std::any::Any;
+
+use fixedbitset::FixedBitSet;
+
+use crate::simulation::Simulation;
+
+// Block rules by name, in the "MS,D" notation `parse_rule` reads
+const NAMED_RULES: [(&str, [u8; 16]); 3] = [
+    ("critters", [15, 14, 13, 3, 11, 5, 6, 1, 7, 9, 10, 2, 12, 4, 8, 0]),
+    ("bbm", [0, 8, 4, 3, 2, 5, 9, 7, 1, 6, 10, 11, 12, 13, 14, 15]),
+    ("tron", [15, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 0]),
+];
+
+/// A block cellular automaton on the Margolus neighborhood.
+///
+/// The universe is cut into 2x2 blocks and every block is replaced as a whole
+/// by the rule, a table from the 16 possible blocks to their successors. A block
+/// is the sum of 1 for the north west, 2 for the north east, 4 for the south
+/// west and 8 for the south east cell. Odd generations move the blocks one cell
+/// down and to the right, wrapping around the edges, so the width and height
+/// are kept even. When the table is a permutation the rule is reversible and
+/// generations can be stepped backward.
+pub struct Margolus {
+    width: usize,
+    height: usize,
+    generation: u64,
+    table: [u8; 16],
+    inverse: Option<[u8; 16]>,
+    cells: FixedBitSet,
+    live_cells: Vec<(f32,f32,f32)>
+}
+
+impl Margolus {
+
+    /// Creates an empty universe running Critters.
+    pub fn new(width: usize, height: usize) -> Margolus {
+        let (width, height) = (Margolus::even(width), Margolus::even(height));
+        let table = NAMED_RULES[0].1;
+        Margolus {
+            width,
+            height,
+            generation: 0,
+            table,
+            inverse: Margolus::invert(&table),
+            cells: FixedBitSet::with_capacity(width * height),
+            live_cells: Vec::new()
+        }
+    }
+
+    /// Parses a rule name such as "critters", "bbm" or "tron", or a table of 16
+    /// successors in Golly's "MS,D0;8;4;3;2;5;9;7;1;6;10;11;12;13;14;15" form.
+    fn parse_rule(rule: &str) -> Result<[u8; 16], String> {
+        let rule = rule.trim();
+        if let Some((_, table)) = NAMED_RULES.iter().find(|(name, _)| name.eq_ignore_ascii_case(rule)) {
+            return Ok(*table);
+        }
+        let values = rule.strip_prefix("MS,D")
+            .ok_or_else(|| format!("'{}' is not a Margolus rule such as critters or MS,D0;8;4;...", rule))?;
+        let mut table = [0; 16];
+        let mut count = 0;
+        for (i, value) in values.split(';').enumerate() {
+            match value.trim().parse::<u8>() {
+                Ok(block) if block < 16 && i < 16 => table[i] = block,
+                _ => return Err(format!("'{}' is not a block between 0 and 15", value)),
+            }
+            count += 1;
+        }
+        if count != 16 {
+            return Err(format!("rule '{}' should list 16 blocks", rule));
+        }
+        Ok(table)
+    }
+
+    /// Gets the table undoing a rule, if every block has exactly one predecessor.
+    fn invert(table: &[u8; 16]) -> Option<[u8; 16]> {
+        let mut inverse = [16; 16];
+        for (block, next) in table.iter().enumerate() {
+            if inverse[*next as usize] != 16 {
+                return None;
+            }
+            inverse[*next as usize] = block as u8;
+        }
+        Some(inverse)
+    }
+
+    /// Rounds a size down to an even number of cells, at least one block.
+    fn even(size: usize) -> usize {
+        (size / 2 * 2).max(2)
+    }
+
+    /// Replaces every block of the partition starting at `offset` through `table`.
+    fn apply(&mut self, table: &[u8; 16], offset: usize) {
+        for block_row in (0..self.height).step_by(2) {
+            for block_col in (0..self.width).step_by(2) {
+                let rows = [(block_row + offset) % self.height, (block_row + offset + 1) % self.height];
+                let cols = [(block_col + offset) % self.width, (block_col + offset + 1) % self.width];
+                let indices = [
+                    rows[0] * self.width + cols[0],
+                    rows[0] * self.width + cols[1],
+                    rows[1] * self.width + cols[0],
+                    rows[1] * self.width + cols[1],
+                ];
+                let block = indices.iter().enumerate()
+                    .fold(0, |block, (bit, idx)| block | (self.cells[*idx] as usize) << bit);
+                let next = table[block];
+                for (bit, idx) in indices.iter().enumerate() {
+                    self.cells.set(*idx, next >> bit & 1 == 1);
+                }
+            }
+        }
+    }
+
+    fn refresh_live_cell_list(&mut self) {
+        self.live_cells.clear();
+        for idx in self.cells.ones() {
+            let (row, col) = (idx / self.width, idx % self.width);
+            self.live_cells.push((row as f32, col as f32, 1.0));
+        }
+    }
+}
+
+impl Simulation for Margolus {
+    fn width(&self) -> usize {
+        self.width
+    }
+
+    fn height(&self) -> usize {
+        self.height
+    }
+
+    fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    fn tick(&mut self) {
+        let table = self.table;
+        self.apply(&table, (self.generation % 2) as usize);
+        self.generation += 1;
+        self.refresh_live_cell_list();
+    }
+
+    /// Undoes the last generation by running the inverse table on the same blocks.
+    fn step_back(&mut self) -> Result<(), String> {
+        let inverse = self.inverse.ok_or_else(|| String::from("The Margolus rule is not reversible"))?;
+        if self.generation == 0 {
+            return Err(String::from("There is no generation before generation 0"));
+        }
+        self.generation -= 1;
+        self.apply(&inverse, (self.generation % 2) as usize);
+        self.refresh_live_cell_list();
+        Ok(())
+    }
+
+    fn rule(&self) -> String {
+        let blocks: Vec<String> = self.table.iter().map(|block| block.to_string()).collect();
+        format!("MS,D{}", blocks.join(";"))
+    }
+
+    fn set_rule(&mut self, rule: &str) -> Result<(), String> {
+        self.table = Margolus::parse_rule(rule)?;
+        self.inverse = Margolus::invert(&self.table);
+        Ok(())
+    }
+
+    fn state_count(&self) -> u8 {
+        2
+    }
+
+    fn get_live_cells(&self) -> &[(f32,f32,f32)] {
+        &self.live_cells
+    }
+
+    fn toggle_cell(&mut self, row: usize, col: usize) {
+        self.toggle_cells(&[(row, col)]);
+    }
+
+    fn toggle_cells(&mut self, cells: &[(usize, usize)]) {
+        for (row, col) in cells {
+            self.cells.toggle(row * self.width + col);
+        }
+        self.refresh_live_cell_list();
+    }
+
+    /// Cells past the even width and height are dropped.
+    fn set_cells(&mut self, cells: &[(usize, usize)]) {
+        self.cells.clear();
+        for (row, col) in cells {
+            if *row < self.height && *col < self.width {
+                self.cells.insert(row * self.width + col);
+            }
+        }
+        self.refresh_live_cell_list();
+    }
+
+    fn set_size(&mut self, width: Option<usize>, height: Option<usize>) {
+        if let Some(w) = width {
+            self.width = Margolus::even(w);
+        }
+        if let Some(h) = height {
+            self.height = Margolus::even(h);
+        }
+        if width.is_some() || height.is_some() {
+            self.generation = 0;
+            self.cells = FixedBitSet::with_capacity(self.width * self.height);
+            self.refresh_live_cell_list();
+        }
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Margolus, NAMED_RULES};
+    use crate::random;
+    use crate::simulation::Simulation;
+
+    #[test]
+    fn named_rules_are_reversible() {
+        for (name, table) in NAMED_RULES {
+            let inverse = Margolus::invert(&table).unwrap();
+            assert!((0..16).all(|block| inverse[table[block] as usize] == block as u8), "{}", name);
+        }
+        assert!(Margolus::invert(&[0; 16]).is_none());
+    }
+
+    #[test]
+    fn parses_rules() {
+        let mut margolus = Margolus::new(10, 10);
+        margolus.set_rule("BBM").unwrap();
+        assert_eq!(margolus.rule(), "MS,D0;8;4;3;2;5;9;7;1;6;10;11;12;13;14;15");
+        margolus.set_rule(&margolus.rule()).unwrap();
+        assert_eq!(margolus.table, NAMED_RULES[1].1);
+        assert!(margolus.set_rule("MS,D0;1;2").is_err());
+        assert!(margolus.set_rule("MS,D0;1;2;3;4;5;6;7;8;9;10;11;12;13;14;16").is_err());
+        assert!(margolus.set_rule("B3/S23").is_err());
+    }
+
+    #[test]
+    fn stepping_back_restores_the_start() {
+        for (name, _) in NAMED_RULES {
+            let mut margolus = Margolus::new(31, 24);
+            assert_eq!((margolus.width(), margolus.height()), (30, 24));
+            margolus.set_rule(name).unwrap();
+            let cells: Vec<(usize, usize)> = (8..16)
+                .flat_map(|row| (10..20).map(move |col| (row, col)))
+                .filter(|(row, col)| random::unit(99, 0, row * 30 + col, 0) < 0.375)
+                .collect();
+            margolus.set_cells(&cells);
+            let start = margolus.get_live_cells().to_vec();
+            for _ in 0..50 {
+                margolus.tick();
+            }
+            assert_ne!(margolus.get_live_cells(), &start[..], "{}", name);
+            for _ in 0..50 {
+                margolus.step_back().unwrap();
+            }
+            assert_eq!(margolus.get_live_cells(), &start[..], "{}", name);
+            assert!(margolus.step_back().is_err());
+        }
+    }
+}
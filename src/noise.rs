@@ -0,0 +1,125 @@
+use std::collections::BTreeMap;
+use std::fmt;
+
+use crate::random;
+
+// Separate random numbers drawn for the same cell in the same generation
+const TRANSITION_STREAM: u64 = 1;
+const TEMPERATURE_STREAM: u64 = 2;
+
+/// Randomness layered over a rule, written like "B6=0.05 S3=0.8 T=0.001 seed=42".
+///
+/// "Bn=p" makes a dead cell with n live neighbors come alive with probability p
+/// and "Sn=p" keeps a live cell with n live neighbors alive with probability p,
+/// in place of what the rule says for that count. Every other count follows the
+/// rule. The temperature "T=p" is the chance of each cell flipping between dead
+/// and alive after every generation.
+///
+/// Random numbers come from `random::unit`, so a run is the same from the same seed.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Noise {
+    birth: BTreeMap<usize, f64>,
+    survival: BTreeMap<usize, f64>,
+    temperature: f64,
+    seed: u64,
+}
+
+impl Noise {
+
+    /// Parses settings separated by spaces or commas, an empty string has no randomness.
+    pub fn parse(text: &str) -> Result<Noise, String> {
+        let mut noise = Noise::default();
+        for setting in text.split([' ', ',']).filter(|s| !s.is_empty()) {
+            let (key, value) = setting.split_once('=').ok_or_else(|| format!("'{}' should be key=value", setting))?;
+            if key.eq_ignore_ascii_case("seed") {
+                noise.seed = value.parse().map_err(|_| format!("'{}' is not a whole number seed", setting))?;
+                continue;
+            }
+            let probability = match value.parse::<f64>() {
+                Ok(p) if (0.0..=1.0).contains(&p) => p,
+                _ => return Err(format!("'{}' is not a probability between 0 and 1", setting)),
+            };
+            let mut chars = key.chars();
+            let kind = chars.next().map(|c| c.to_ascii_uppercase());
+            let count = chars.as_str();
+            match kind {
+                Some('T') if count.is_empty() => noise.temperature = probability,
+                Some(kind @ ('B' | 'S')) => {
+                    let count = count.parse::<usize>().map_err(|_| format!("'{}' needs a neighbor count after {}", setting, kind))?;
+                    let probabilities = if kind == 'B' { &mut noise.birth } else { &mut noise.survival };
+                    probabilities.insert(count, probability);
+                }
+                _ => return Err(format!("'{}' is not a Bn, Sn, T or seed setting", key)),
+            }
+        }
+        Ok(noise)
+    }
+
+    /// Gets whether every transition follows the rule exactly.
+    pub fn is_deterministic(&self) -> bool {
+        self.birth.is_empty() && self.survival.is_empty() && self.temperature == 0.0
+    }
+
+    /// Gets the state of a cell in the next generation given its current state,
+    /// its live neighbor count and `next`, the state the rule alone gives it.
+    ///
+    /// `born_or_survives` turns a birth or survival decision into a state the way
+    /// the rule would, so cells failing to survive start dying as usual.
+    pub fn next_state(
+        &self,
+        state: u8,
+        live_neighbors: usize,
+        next: u8,
+        born_or_survives: impl Fn(bool) -> u8,
+        generation: u64,
+        idx: usize,
+    ) -> u8 {
+        let probability = match state {
+            0 => self.birth.get(&live_neighbors),
+            1 => self.survival.get(&live_neighbors),
+            _ => None,
+        };
+        let next = match probability {
+            Some(p) => born_or_survives(random::unit(self.seed, generation, idx, TRANSITION_STREAM) < *p),
+            None => next,
+        };
+        if self.temperature > 0.0 && random::unit(self.seed, generation, idx, TEMPERATURE_STREAM) < self.temperature {
+            if next == 0 { 1 } else { 0 }
+        } else {
+            next
+        }
+    }
+}
+
+impl fmt::Display for Noise {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (count, p) in self.birth.iter() {
+            write!(f, "B{}={} ", count, p)?;
+        }
+        for (count, p) in self.survival.iter() {
+            write!(f, "S{}={} ", count, p)?;
+        }
+        if self.temperature > 0.0 {
+            write!(f, "T={} ", self.temperature)?;
+        }
+        write!(f, "seed={}", self.seed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Noise;
+
+    #[test]
+    fn parses_settings() {
+        let noise = Noise::parse("s3=0.8, B6=0.05 T=0.001 seed=42").unwrap();
+        assert_eq!(noise.to_string(), "B6=0.05 S3=0.8 T=0.001 seed=42");
+        assert_eq!(Noise::parse(&noise.to_string()).unwrap(), noise);
+        assert!(Noise::parse("").unwrap().is_deterministic());
+        assert!(!Noise::parse("T=0.5").unwrap().is_deterministic());
+        assert!(Noise::parse("B3=1.5").is_err());
+        assert!(Noise::parse("T3=0.5").is_err());
+        assert!(Noise::parse("B=0.5").is_err());
+        assert!(Noise::parse("seed=-1").is_err());
+    }
+}
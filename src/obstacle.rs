@@ -0,0 +1,50 @@
+/// A cell the rule leaves alone, placed with the mouse to build walls and
+/// reflectors.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Obstacle {
+    /// Always dead.
+    Wall,
+    /// Always alive.
+    Alive,
+    /// Alive for `period` generations, then dead for `period` generations, and so on.
+    Emitter { period: u64 },
+}
+
+impl Obstacle {
+
+    /// Parses the name of an obstacle as used by the brush select, e.g. "wall".
+    /// Only emitters use the period.
+    pub fn parse(name: &str, period: u64) -> Result<Obstacle, String> {
+        match name.trim().to_ascii_lowercase().as_str() {
+            "wall" => Ok(Obstacle::Wall),
+            "alive" => Ok(Obstacle::Alive),
+            "emitter" if period >= 1 => Ok(Obstacle::Emitter { period }),
+            "emitter" => Err(String::from("an emitter needs a period of at least 1")),
+            _ => Err(format!("'{}' is not an obstacle", name)),
+        }
+    }
+
+    /// Gets the state of the cell in a generation, 0 for dead and 1 for alive.
+    pub fn state(&self, generation: u64) -> u8 {
+        match self {
+            Obstacle::Wall => 0,
+            Obstacle::Alive => 1,
+            Obstacle::Emitter { period } => (generation / period).is_multiple_of(2) as u8,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Obstacle;
+
+    #[test]
+    fn emitters_toggle_every_period() {
+        let emitter = Obstacle::parse("Emitter", 3).unwrap();
+        let states: Vec<u8> = (0..8).map(|generation| emitter.state(generation)).collect();
+        assert_eq!(states, [1, 1, 1, 0, 0, 0, 1, 1]);
+        assert_eq!(Obstacle::parse("wall", 0).unwrap().state(5), 0);
+        assert!(Obstacle::parse("emitter", 0).is_err());
+        assert!(Obstacle::parse("door", 1).is_err());
+    }
+}
@@ -0,0 +1,102 @@
+use std::ops::Range;
+
+// Fewest cells in a band worth the cost of handing it to another thread
+pub const MIN_BAND_CELLS: usize = 256 * 256;
+
+/// Splits `0..len` into about one band per thread, none shorter than `min_band`
+/// unless `len` itself is.
+pub fn bands(len: usize, min_band: usize) -> Vec<Range<usize>> {
+    split(len, (len / min_band.max(1)).clamp(1, thread_count()))
+}
+
+/// Splits `0..len` into `count` bands of about the same length, fewer when `len`
+/// is shorter than `count`.
+pub fn split(len: usize, count: usize) -> Vec<Range<usize>> {
+    let size = len.div_ceil(count.max(1)).max(1);
+    (0..len).step_by(size).map(|start| start..(start + size).min(len)).collect()
+}
+
+/// Maps every band through `f`, keeping their order.
+///
+/// With the `parallel` feature native builds run the bands on rayon's thread
+/// pool. The wasm build leaves rayon out and runs them one after another, as
+/// does a build without the feature.
+pub fn map_bands<T, F>(bands: Vec<Range<usize>>, f: F) -> Vec<T>
+where
+    T: Send,
+    F: Fn(Range<usize>) -> T + Sync + Send,
+{
+    #[cfg(all(feature = "parallel", not(target_arch = "wasm32")))]
+    {
+        use rayon::prelude::*;
+        bands.into_par_iter().map(f).collect()
+    }
+    #[cfg(any(not(feature = "parallel"), target_arch = "wasm32"))]
+    {
+        bands.into_iter().map(f).collect()
+    }
+}
+
+/// Runs `f` on every band with the part of `out` it covers, the bands must cover
+/// `out` in order.
+///
+/// The bands run like those of `map_bands`, each writing straight into `out`.
+pub fn fill_bands<T, F>(out: &mut [T], bands: Vec<Range<usize>>, f: F)
+where
+    T: Send,
+    F: Fn(Range<usize>, &mut [T]) + Sync + Send,
+{
+    let mut parts = Vec::with_capacity(bands.len());
+    let mut rest = out;
+    for band in bands {
+        let (part, tail) = std::mem::take(&mut rest).split_at_mut(band.len());
+        parts.push((band, part));
+        rest = tail;
+    }
+    #[cfg(all(feature = "parallel", not(target_arch = "wasm32")))]
+    {
+        use rayon::prelude::*;
+        parts.into_par_iter().for_each(|(band, part)| f(band, part));
+    }
+    #[cfg(any(not(feature = "parallel"), target_arch = "wasm32"))]
+    {
+        parts.into_iter().for_each(|(band, part)| f(band, part));
+    }
+}
+
+fn thread_count() -> usize {
+    #[cfg(all(feature = "parallel", not(target_arch = "wasm32")))]
+    {
+        rayon::current_num_threads()
+    }
+    #[cfg(any(not(feature = "parallel"), target_arch = "wasm32"))]
+    {
+        1
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{bands, split};
+
+    #[test]
+    fn bands_cover_the_range() {
+        for (len, min_band) in [(1, 4), (10, 4), (100, 7), (4096, 16)] {
+            let bands = bands(len, min_band);
+            assert!(!bands.is_empty());
+            let mut next = 0;
+            for band in bands {
+                assert_eq!(band.start, next);
+                next = band.end;
+            }
+            assert_eq!(next, len);
+        }
+    }
+
+    #[test]
+    fn split_gives_the_requested_band_count() {
+        assert_eq!(split(10, 3), vec![0..4, 4..8, 8..10]);
+        assert_eq!(split(2, 5), vec![0..1, 1..2]);
+        assert_eq!(split(0, 3), vec![]);
+    }
+}
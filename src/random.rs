@@ -0,0 +1,33 @@
+/// Gets a number in 0..1 for a cell in a generation, the same every time for the
+/// same seed. Each use of random numbers passes its own `stream`, so draws for
+/// the same cell and generation are independent of each other.
+///
+/// This is a hash rather than a sequence, so the numbers do not depend on the
+/// order cells are visited in or how the universe is split into bands.
+pub fn unit(seed: u64, generation: u64, idx: usize, stream: u64) -> f64 {
+    // The SplitMix64 finalizer over the seed mixed with the other inputs
+    let mut z = seed
+        ^ generation.wrapping_mul(0x9e37_79b9_7f4a_7c15)
+        ^ (idx as u64).wrapping_mul(0xc2b2_ae3d_27d4_eb4f)
+        ^ stream.wrapping_mul(0x1656_67b1_9e37_79f9);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^= z >> 31;
+    (z >> 11) as f64 / (1u64 << 53) as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::unit;
+
+    #[test]
+    fn numbers_are_spread_and_repeatable() {
+        let numbers: Vec<f64> = (0..10000).map(|idx| unit(7, 3, idx, 1)).collect();
+        assert!(numbers.iter().all(|n| (0.0..1.0).contains(n)));
+        let below_half = numbers.iter().filter(|n| **n < 0.5).count();
+        assert!((4800..5200).contains(&below_half));
+        assert_eq!(unit(7, 3, 17, 1), numbers[17]);
+        assert_ne!(unit(8, 3, 17, 1), numbers[17]);
+        assert_ne!(unit(7, 3, 17, 2), numbers[17]);
+    }
+}
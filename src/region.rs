@@ -0,0 +1,69 @@
+use std::ops::RangeInclusive;
+
+/// A rectangle of cells to paint with a rule, written like "B36/S23 cols=64..127".
+///
+/// "rows=a..b" and "cols=a..b" bound the rectangle, both ends included, and
+/// either can be a single row or column such as "rows=5". Without bounds the
+/// rectangle is the whole universe. Everything else is the rule.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Region {
+    pub rule: String,
+    rows: Option<RangeInclusive<usize>>,
+    cols: Option<RangeInclusive<usize>>,
+}
+
+impl Region {
+
+    pub fn parse(text: &str) -> Result<Region, String> {
+        let mut rule = Vec::new();
+        let mut rows = None;
+        let mut cols = None;
+        for part in text.split_whitespace() {
+            let (key, value) = part.split_once('=').unwrap_or((part, ""));
+            let bounds = match key.to_ascii_lowercase().as_str() {
+                "rows" => &mut rows,
+                "cols" => &mut cols,
+                _ => {
+                    rule.push(part);
+                    continue;
+                }
+            };
+            let (first, last) = value.split_once("..").unwrap_or((value, value));
+            *bounds = match (first.parse::<usize>(), last.parse::<usize>()) {
+                (Ok(first), Ok(last)) if first <= last => Some(first..=last),
+                _ => return Err(format!("'{}' is not a range such as cols=64..127", part)),
+            };
+        }
+        if rule.is_empty() {
+            return Err(format!("region '{}' needs a rule", text.trim()));
+        }
+        Ok(Region { rule: rule.join(" "), rows, cols })
+    }
+
+    /// Gets the cells of the rectangle inside a universe.
+    pub fn cells(&self, width: usize, height: usize) -> Vec<(usize, usize)> {
+        let clip = |bounds: &Option<RangeInclusive<usize>>, size: usize| match bounds {
+            Some(bounds) => *bounds.start()..(*bounds.end() + 1).min(size),
+            None => 0..size,
+        };
+        let cols = clip(&self.cols, width);
+        clip(&self.rows, height).flat_map(|row| cols.clone().map(move |col| (row, col))).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Region;
+
+    #[test]
+    fn parses_regions() {
+        let region = Region::parse("QuadLife B36/S23 cols=2..3 rows=9").unwrap();
+        assert_eq!(region.rule, "QuadLife B36/S23");
+        assert_eq!(region.cells(10, 10), [(9, 2), (9, 3)]);
+        assert_eq!(Region::parse("B3/S23 cols=8..20").unwrap().cells(10, 2).len(), 4);
+        assert_eq!(Region::parse("B3/S23").unwrap().cells(4, 3).len(), 12);
+        assert!(Region::parse("cols=1..2").is_err());
+        assert!(Region::parse("B3/S23 rows=5..2").is_err());
+        assert!(Region::parse("B3/S23 rows=a").is_err());
+    }
+}
@@ -5,35 +5,41 @@ use js_sys::WebAssembly;
 use wasm_bindgen::prelude::*;
 use wasm_bindgen::JsCast;
 // use crate::utils;
-use crate::universe::Universe;
+use crate::simulation::Simulation;
 
 pub struct Renderer {
     canvas: web_sys::HtmlCanvasElement,
     ctx: web_sys::WebGl2RenderingContext,
-    universe: Rc<RefCell<Universe>>,
+    universe: Rc<RefCell<Box<dyn Simulation>>>,
     view_scale: f64,
     view_position: (i32, i32),
     view_start_position: Option<(i32, i32)>,
     cell_program: web_sys::WebGlProgram,
     bg_program: web_sys::WebGlProgram,
+    bg_buffer: web_sys::WebGlBuffer,
+    polygon_buffer: web_sys::WebGlBuffer,
     cell_position_loc: u32,
+    cell_color_loc: u32,
     bg_position_loc: u32,
     point_size_loc: web_sys::WebGlUniformLocation,
     universe_width_loc: web_sys::WebGlUniformLocation,
     universe_height_loc: web_sys::WebGlUniformLocation,
     universe_width_offset_loc: web_sys::WebGlUniformLocation,
-    universe_height_offset_loc: web_sys::WebGlUniformLocation
+    universe_height_offset_loc: web_sys::WebGlUniformLocation,
+    hex_shift_loc: web_sys::WebGlUniformLocation
 }
 
 const CELL_SIZE: f32 = 10.0;
 const CANVAS_COLOR: [f32; 4] = [0.2, 0.2, 0.2, 1.0];
+const TILE_OUTLINE_COLOR: [f32; 3] = [0.25, 0.25, 0.25];
+const REGION_EDGE_COLOR: [f32; 3] = [0.95, 0.75, 0.2];
 // const UNIVERSE_COLOR: [f32; 4] = [0.3, 0.3, 0.3, 1.0];
 // const ALIVE_COLOR: [f32; 4] = [0.8, 0.8, 0.8, 1.0];
 
 impl Renderer {
     pub fn new(
         canvas: web_sys::HtmlCanvasElement,
-        universe: Rc<RefCell<Universe>>,
+        universe: Rc<RefCell<Box<dyn Simulation>>>,
     ) -> Result<Renderer, JsValue> {
 
         let ctx: web_sys::WebGl2RenderingContext = canvas.get_context("webgl2")?
@@ -46,19 +52,26 @@ impl Renderer {
             r##"#version 300 es
     
             in vec2 position;
+            in vec3 color;
             uniform float universeWidth;
             uniform float universeHeight;
             uniform float universeWidthOffset;
             uniform float universeHeightOffset;
+            uniform float hexShift;
             uniform float pointSize;
+            out vec3 cellColor;
 
             void main() {
 
-                float x = 2.0 * ((position[0] / universeWidth) - 0.5) + universeWidthOffset;
-                float y = 2.0 * ((position[1] / universeHeight) - 0.5) + universeHeightOffset;
+                // position is (row, column), rows run down the screen and columns across
+                float x = 2.0 * ((position[0] / universeHeight) - 0.5) + universeHeightOffset;
+                // On a hexagonal grid every odd row is moved half a cell to the right
+                float col = position[1] + hexShift * mod(position[0], 2.0);
+                float y = 2.0 * ((col / universeWidth) - 0.5) + universeWidthOffset;
 
                 gl_Position = vec4(y, -x, 0.0, 1.0);
                 gl_PointSize = pointSize;
+                cellColor = color;
             }
             "##,
         ).expect("failed to compile vert cell shader");
@@ -82,9 +95,10 @@ impl Renderer {
             r##"#version 300 es
         
             precision lowp float;
+            in vec3 cellColor;
             out vec4 outColor;
             void main() {
-                outColor = vec4(0.8, 0.8, 0.8, 1.0);
+                outColor = vec4(cellColor, 1.0);
             }
             "##,
         ).expect("falied to compile frag shader");
@@ -104,17 +118,20 @@ impl Renderer {
     
         let cell_program = Renderer::link_program(&ctx, &vert_cell_shader, &frag_cell_shader).expect("Couldnt link shaders to cell_program");
         let cell_position_loc = ctx.get_attrib_location(&cell_program, "position") as u32;
+        let cell_color_loc = ctx.get_attrib_location(&cell_program, "color") as u32;
         let point_size_loc = ctx.get_uniform_location(&cell_program, "pointSize").unwrap();
         let universe_width_loc = ctx.get_uniform_location(&cell_program, "universeWidth").unwrap();
         let universe_height_loc = ctx.get_uniform_location(&cell_program, "universeHeight").unwrap();
         let universe_width_offset_loc = ctx.get_uniform_location(&cell_program, "universeWidthOffset").unwrap();
         let universe_height_offset_loc = ctx.get_uniform_location(&cell_program, "universeHeightOffset").unwrap();
+        let hex_shift_loc = ctx.get_uniform_location(&cell_program, "hexShift").unwrap();
 
         let bg_program = Renderer::link_program(&ctx, &vert_bg_shader, &frag_bg_shader).expect("Couldnt link shaders to bg_program");
         let bg_position_loc = ctx.get_attrib_location(&bg_program, "position") as u32;
 
         ctx.use_program(Some(&bg_program));
-        Renderer::init_background(&ctx).unwrap();
+        let bg_buffer = Renderer::init_background(&ctx).unwrap();
+        let polygon_buffer = ctx.create_buffer().ok_or_else(|| JsValue::from_str("Failed to create the buffer object"))?;
 
         let view_scale = 1.0;
         let view_position = (0, 0);
@@ -129,13 +146,17 @@ impl Renderer {
             view_start_position,
             cell_program,
             bg_program,
+            bg_buffer,
+            polygon_buffer,
             cell_position_loc,
+            cell_color_loc,
             bg_position_loc,
             point_size_loc,
             universe_width_loc,
             universe_height_loc,
             universe_width_offset_loc,
-            universe_height_offset_loc
+            universe_height_offset_loc,
+            hex_shift_loc
         })
     }
 
@@ -189,7 +210,7 @@ impl Renderer {
         }
     }
 
-    fn init_background(context: &web_sys::WebGl2RenderingContext) -> Result<(), JsValue> {
+    fn init_background(context: &web_sys::WebGl2RenderingContext) -> Result<web_sys::WebGlBuffer, JsValue> {
         let vertices: [f32; 8] = [
             -1.0, 1.0, 
             -1.0, -1.0, 
@@ -210,7 +231,7 @@ impl Renderer {
         };
         context.bind_buffer(web_sys::WebGl2RenderingContext::ARRAY_BUFFER, Some(&vertex_buffer));
         context.buffer_data_with_array_buffer_view(web_sys::WebGl2RenderingContext::ARRAY_BUFFER, &vertex_array, web_sys::WebGl2RenderingContext::STATIC_DRAW);
-        Ok(())
+        Ok(vertex_buffer)
     }
 
     pub fn draw(&self) {
@@ -220,18 +241,40 @@ impl Renderer {
         self.canvas.set_width(canvas_width as u32);
         self.canvas.set_height(canvas_height as u32);
         // log!("({}, {})", canvas.width(), canvas.height());
-        let canvas_small_side = canvas_width.min(canvas_height);
-        let view_scale = ((canvas_small_side as f64) * self.view_scale) as i32;
-
-        let viewport_offset_x = (canvas_width - view_scale) / 2 - self.view_position.0;
-        let viewport_offset_y = (canvas_height - view_scale) / 2 + self.view_position.1;
-        self.ctx.viewport(viewport_offset_x, viewport_offset_y, view_scale, view_scale);
+        let (viewport_offset_x, viewport_offset_y, viewport_width, viewport_height) = self.viewport();
+        self.ctx.viewport(viewport_offset_x, viewport_offset_y, viewport_width, viewport_height);
 
         self.ctx.clear_color(CANVAS_COLOR[0], CANVAS_COLOR[1], CANVAS_COLOR[2], CANVAS_COLOR[3]);
         self.ctx.clear(web_sys::WebGl2RenderingContext::COLOR_BUFFER_BIT);
 
         self.draw_background();
-        self.draw_cells(view_scale as f32);
+        if self.universe.borrow().polygons().is_some() {
+            self.draw_polygons();
+        } else {
+            self.draw_cells(viewport_width as f32);
+        }
+        if self.universe.borrow().region_edges().is_some_and(|edges| !edges.is_empty()) {
+            self.draw_region_edges();
+        }
+    }
+
+    /// Gets the row and column of the cell under a point on the page.
+    ///
+    /// The position can be outside of the universe when the point is off of it.
+    pub fn get_cell_position(&self, client_x: i32, client_y: i32) -> (isize, isize) {
+        let (viewport_offset_x, viewport_offset_y, viewport_width, viewport_height) = self.viewport();
+        let bounding_rect = (self.canvas.as_ref() as &web_sys::Element).get_bounding_client_rect();
+        let universe = self.universe.borrow();
+        // The webgl viewport is measured from the bottom of the canvas
+        let viewport_top = self.canvas.height() as i32 - viewport_offset_y - viewport_height;
+        let x = client_x as f64 - bounding_rect.left() - viewport_offset_x as f64;
+        let y = client_y as f64 - bounding_rect.top() - viewport_top as f64;
+        let row = (y * universe.height() as f64 / viewport_height as f64).floor() as isize;
+        let mut col = x * universe.width() as f64 / viewport_width as f64;
+        if universe.hexagonal() && row.rem_euclid(2) == 1 {
+            col -= 0.5;
+        }
+        (row, col.floor() as isize)
     }
 
     pub fn reset_view(&mut self) {
@@ -272,16 +315,30 @@ impl Renderer {
         self.view_scale = f64::max(self.view_scale * scale_delta, 0.1);
     }
 
-    pub fn get_view_position(&self) -> (i32, i32) {
-        self.view_position
-    }
-
     pub fn get_view_scale(&self) -> f64 {
         self.view_scale
     }
 
+    /// Gets the x, y, width and height of the area of the canvas the universe is drawn in.
+    ///
+    /// Cells stay square, so the universe fills the canvas along whichever side is tighter.
+    fn viewport(&self) -> (i32, i32, i32, i32) {
+        let canvas_width = self.canvas.width() as i32;
+        let canvas_height = self.canvas.height() as i32;
+        let universe = self.universe.borrow();
+        let universe_width = universe.width() as f64;
+        let universe_height = universe.height() as f64;
+        let cell_size = f64::min(canvas_width as f64 / universe_width, canvas_height as f64 / universe_height) * self.view_scale;
+        let viewport_width = (cell_size * universe_width) as i32;
+        let viewport_height = (cell_size * universe_height) as i32;
+        let viewport_offset_x = (canvas_width - viewport_width) / 2 - self.view_position.0;
+        let viewport_offset_y = (canvas_height - viewport_height) / 2 + self.view_position.1;
+        (viewport_offset_x, viewport_offset_y, viewport_width, viewport_height)
+    }
+
     fn draw_background(&self) {
         self.ctx.use_program(Some(&self.bg_program));
+        self.ctx.bind_buffer(web_sys::WebGl2RenderingContext::ARRAY_BUFFER, Some(&self.bg_buffer));
         self.ctx.enable_vertex_attrib_array(self.bg_position_loc);
         self.ctx.vertex_attrib_pointer_with_i32(self.bg_position_loc, 2, web_sys::WebGl2RenderingContext::FLOAT, false, 0, 0);
 
@@ -295,16 +352,85 @@ impl Renderer {
         let universe = self.universe.borrow();
         let universe_width = universe.width() as f32;
         let universe_height = universe.height() as f32;
-        let cell_size = size / (universe_width.min(universe_height) + CELL_SIZE);
+        let cell_size = size / (universe_width + CELL_SIZE);
         self.ctx.uniform1f(Some(&self.point_size_loc), cell_size);
         self.ctx.uniform1f(Some(&self.universe_width_loc), universe_width);
         self.ctx.uniform1f(Some(&self.universe_height_loc), universe_height);
         self.ctx.uniform1f(Some(&self.universe_width_offset_loc),  1.0 / universe_width);
         self.ctx.uniform1f(Some(&self.universe_height_offset_loc), 1.0 / universe_height);
+        self.ctx.uniform1f(Some(&self.hex_shift_loc), if universe.hexagonal() { 0.5 } else { 0.0 });
+        // Every state is drawn in its color from the engine, e.g. from a rule table's @COLORS
+        let palette = universe.palette();
         let live_cells = universe.get_live_cells();
-        for (row, col) in live_cells {
+        for (row, col, state) in live_cells {
+            let [r, g, b] = palette[(*state as usize).min(palette.len() - 1)];
             self.ctx.vertex_attrib2f(self.cell_position_loc, *row, *col);
+            self.ctx.vertex_attrib3f(self.cell_color_loc, r, g, b);
             self.ctx.draw_arrays(web_sys::WebGl2RenderingContext::POINTS, 0, 1);
         }
     }
+
+    /// Draws the cells of engines made of polygons, filling the live ones in their
+    /// state's color and outlining every tile.
+    fn draw_polygons(&self) {
+        self.ctx.use_program(Some(&self.cell_program));
+        let universe = self.universe.borrow();
+        let (polygons, states) = universe.polygons().unwrap();
+        self.ctx.uniform1f(Some(&self.universe_width_loc), universe.width() as f32);
+        self.ctx.uniform1f(Some(&self.universe_height_loc), universe.height() as f32);
+        // Corners are positions already, not the top left of a cell
+        self.ctx.uniform1f(Some(&self.universe_width_offset_loc), 0.0);
+        self.ctx.uniform1f(Some(&self.universe_height_offset_loc), 0.0);
+        self.ctx.uniform1f(Some(&self.hex_shift_loc), 0.0);
+        let vertices: Vec<f32> = polygons.iter().flatten().flat_map(|(row, col)| [*row, *col]).collect();
+        self.ctx.bind_buffer(web_sys::WebGl2RenderingContext::ARRAY_BUFFER, Some(&self.polygon_buffer));
+        self.ctx.buffer_data_with_array_buffer_view(
+            web_sys::WebGl2RenderingContext::ARRAY_BUFFER,
+            &js_sys::Float32Array::from(&vertices[..]),
+            web_sys::WebGl2RenderingContext::DYNAMIC_DRAW,
+        );
+        self.ctx.enable_vertex_attrib_array(self.cell_position_loc);
+        self.ctx.vertex_attrib_pointer_with_i32(self.cell_position_loc, 2, web_sys::WebGl2RenderingContext::FLOAT, false, 0, 0);
+        let palette = universe.palette();
+        let mut first = 0;
+        for (polygon, state) in polygons.iter().zip(states) {
+            let count = polygon.len() as i32;
+            if *state != 0 {
+                let [r, g, b] = palette[(*state as usize).min(palette.len() - 1)];
+                self.ctx.vertex_attrib3f(self.cell_color_loc, r, g, b);
+                self.ctx.draw_arrays(web_sys::WebGl2RenderingContext::TRIANGLE_FAN, first, count);
+            }
+            let [r, g, b] = TILE_OUTLINE_COLOR;
+            self.ctx.vertex_attrib3f(self.cell_color_loc, r, g, b);
+            self.ctx.draw_arrays(web_sys::WebGl2RenderingContext::LINE_LOOP, first, count);
+            first += count;
+        }
+        self.ctx.disable_vertex_attrib_array(self.cell_position_loc);
+    }
+
+    /// Draws the borders between regions running different rules over the cells.
+    fn draw_region_edges(&self) {
+        self.ctx.use_program(Some(&self.cell_program));
+        let universe = self.universe.borrow();
+        let edges = universe.region_edges().unwrap();
+        self.ctx.uniform1f(Some(&self.universe_width_loc), universe.width() as f32);
+        self.ctx.uniform1f(Some(&self.universe_height_loc), universe.height() as f32);
+        // Edges run along the corners of cells, not through their middles
+        self.ctx.uniform1f(Some(&self.universe_width_offset_loc), 0.0);
+        self.ctx.uniform1f(Some(&self.universe_height_offset_loc), 0.0);
+        self.ctx.uniform1f(Some(&self.hex_shift_loc), 0.0);
+        let vertices: Vec<f32> = edges.iter().flatten().copied().collect();
+        self.ctx.bind_buffer(web_sys::WebGl2RenderingContext::ARRAY_BUFFER, Some(&self.polygon_buffer));
+        self.ctx.buffer_data_with_array_buffer_view(
+            web_sys::WebGl2RenderingContext::ARRAY_BUFFER,
+            &js_sys::Float32Array::from(&vertices[..]),
+            web_sys::WebGl2RenderingContext::DYNAMIC_DRAW,
+        );
+        self.ctx.enable_vertex_attrib_array(self.cell_position_loc);
+        self.ctx.vertex_attrib_pointer_with_i32(self.cell_position_loc, 2, web_sys::WebGl2RenderingContext::FLOAT, false, 0, 0);
+        let [r, g, b] = REGION_EDGE_COLOR;
+        self.ctx.vertex_attrib3f(self.cell_color_loc, r, g, b);
+        self.ctx.draw_arrays(web_sys::WebGl2RenderingContext::LINES, 0, 2 * edges.len() as i32);
+        self.ctx.disable_vertex_attrib_array(self.cell_position_loc);
+    }
 }
@@ -5,7 +5,7 @@ use wasm_bindgen::prelude::*;
 use wasm_bindgen::JsCast;
 
 use crate::renderer::Renderer;
-use crate::universe::Universe;
+use crate::simulation::Simulation;
 // use crate::utils;
 
 
@@ -15,7 +15,8 @@ pub struct RenderLoop {
     window: web_sys::Window,
     playpause_button: web_sys::HtmlButtonElement,
     fps_label: web_sys::HtmlDivElement,
-    universe: Rc<RefCell<Universe>>,
+    generation_label: web_sys::HtmlDivElement,
+    universe: Rc<RefCell<Box<dyn Simulation>>>,
     renderer: Rc<RefCell<Renderer>>,
     ticks_per_frame: usize,
     animation_id: Option<i32>,
@@ -30,13 +31,15 @@ impl RenderLoop {
         window: web_sys::Window,
         playpause_button: web_sys::HtmlButtonElement,
         fps_label: web_sys::HtmlDivElement,
-        universe: Rc<RefCell<Universe>>,
+        generation_label: web_sys::HtmlDivElement,
+        universe: Rc<RefCell<Box<dyn Simulation>>>,
         renderer: Rc<RefCell<Renderer>>,
     ) -> RenderLoop {
         RenderLoop {
             window,
             playpause_button,
             fps_label,
+            generation_label,
             universe,
             renderer,
             ticks_per_frame: 1,
@@ -56,6 +59,7 @@ impl RenderLoop {
                 self.universe.borrow_mut().tick();
             }
             self.renderer.borrow().draw();
+            self.update_generation_label();
             self.then = now;
         }
         
@@ -107,6 +111,23 @@ impl RenderLoop {
         self.frame_durations.clear();
     }
 
+    pub fn update_generation_label(&self) {
+        let universe = self.universe.borrow();
+        let string = format!("Gen {}", universe.generation());
+        (self.generation_label.as_ref() as &web_sys::Node).set_text_content(Some(&string));
+        // Hovering the label shows how much of the universe is still changing
+        let mut title = match universe.active_tiles() {
+            Some(count) => format!("{} active tiles", count),
+            None => String::new(),
+        };
+        // and the population of every color under multi-color rules
+        if let Some(populations) = universe.species_populations() {
+            let populations: Vec<String> = populations.iter().map(|count| count.to_string()).collect();
+            title.push_str(&format!("\nSpecies {}", populations.join(" / ")));
+        }
+        self.generation_label.set_title(&title);
+    }
+
     fn add_frame_duration(&mut self, elapsed: f64) {
         self.frame_durations.push(elapsed);
         if self.frame_durations.len() > FRAME_DURATION_MAX {
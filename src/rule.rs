@@ -0,0 +1,866 @@
+extern crate fixedbitset;
+
+use std::fmt;
+
+use fixedbitset::FixedBitSet;
+
+use crate::hensel;
+use crate::kernel::Kernel;
+
+const MAX_NEIGHBORS: usize = 8;
+const MAX_STATES: u8 = 255;
+const MAX_RANGE: usize = 10;
+// Names of the multi-color rules and how many colors of live cells they have
+const SPECIES_RULES: [(&str, u8); 2] = [("Immigration", 2), ("QuadLife", 4)];
+const WEIGHTED_NAME: &str = "Weighted";
+
+/// The shape of the cells counted around a cell.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Neighborhood {
+    /// Every cell within the range on both axes, a square.
+    Moore,
+    /// Every cell within the range in manhattan distance, a diamond.
+    VonNeumann,
+    /// The six cells around a cell on a hexagonal grid stored as rows where every
+    /// odd row sits half a cell to the right.
+    Hexagonal,
+}
+
+/// A Life-like rule written in B/S notation, e.g. "B3/S23" for Conway's game of life.
+///
+/// `birth` holds the neighbor counts that bring a dead cell to life and
+/// `survival` holds the neighbor counts that keep a live cell alive.
+/// Generations rules such as "B2/S/C3" add a state count: a live cell that does
+/// not survive decays through the refractory states `2..states` before dying.
+/// Larger than Life rules such as "R5,C0,M1,S34..58,B34..45,NM" count the
+/// cells within `range` of a cell instead of only the eight around it.
+/// Isotropic non-totalistic rules such as "B2-a/S12" pick out neighborhoods
+/// with Hensel letters after a count, see `hensel`. A trailing "H" or "V" such as
+/// "B2/S34H" counts the six hexagonal or four von Neumann neighbors instead.
+/// Multi-color rules such as "Immigration" or "QuadLife B36/S23" give every live
+/// cell one of `species` colors, a newborn cell taking the majority color of its
+/// live neighbors.
+/// Cyclic rules such as "R1/T3/C3/NM" step a cell in state k on to state k + 1,
+/// wrapping to 0, when at least a threshold of the cells within range are in
+/// state k + 1 already.
+/// Weighted rules such as "Weighted 1,2,1;2,0,2;1,2,1 B6..8/S4..7" sum the
+/// weights of a kernel over the live cells around a cell, see `Kernel`, and
+/// their B and S parts take weighted sums instead of neighbor counts.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Rule {
+    birth: FixedBitSet,
+    survival: FixedBitSet,
+    states: u8,
+    range: usize,
+    neighborhood: Neighborhood,
+    include_center: bool,
+    // Birth and survival neighborhood masks of isotropic non-totalistic rules
+    isotropic: Option<(FixedBitSet, FixedBitSet)>,
+    species: u8,
+    // Number of neighbors one state ahead that moves a cell on under cyclic rules
+    cyclic_threshold: Option<usize>,
+    // Weights of the cells around a cell under weighted rules, whose birth and
+    // survival sets hold weighted sums less the smallest sum the kernel can give
+    kernel: Option<Kernel>,
+}
+
+impl Rule {
+
+    /// Parses a rule string such as "B36/S23", "B2/S", "S23/B3", "B2-a/S12" or "B2/S34H".
+    ///
+    /// The older "23/3" survival/birth form is accepted as well, and a third
+    /// part such as "/C3" or "/3" makes it a Generations rule with that many states.
+    /// Rules starting with "R" are read as Larger than Life rules, or as cyclic
+    /// rules when their parts are separated by '/'.
+    pub fn parse(rule: &str) -> Result<Rule, String> {
+        let rule = rule.trim();
+        let weighted = rule.get(..WEIGHTED_NAME.len()).filter(|prefix| prefix.eq_ignore_ascii_case(WEIGHTED_NAME));
+        if weighted.is_some() {
+            return Rule::parse_weighted(&rule[WEIGHTED_NAME.len()..]);
+        }
+        for (name, species) in SPECIES_RULES {
+            let named = rule.get(..name.len()).filter(|prefix| prefix.eq_ignore_ascii_case(name));
+            if named.is_some() {
+                return Rule::parse_species(&rule[name.len()..], species);
+            }
+        }
+        if rule.starts_with(['R', 'r']) && rule.contains('/') {
+            return Rule::parse_cyclic(rule);
+        }
+        if rule.starts_with(['R', 'r']) {
+            return Rule::parse_larger_than_life(rule);
+        }
+        let (rule, neighborhood) = match rule.strip_suffix(['H', 'h']) {
+            Some(rest) => (rest, Neighborhood::Hexagonal),
+            None => match rule.strip_suffix(['V', 'v']) {
+                Some(rest) => (rest, Neighborhood::VonNeumann),
+                None => (rule, Neighborhood::Moore),
+            },
+        };
+        let parts: Vec<&str> = rule.split('/').map(|p| p.trim()).collect();
+        if parts.len() != 2 && parts.len() != 3 {
+            return Err(format!("rule '{}' should have two or three parts separated by '/'", rule));
+        }
+        let mut birth = None;
+        let mut survival = None;
+        for (i, part) in parts[..2].iter().enumerate() {
+            let (kind, digits) = match part.chars().next() {
+                Some('B') | Some('b') => ('B', &part[1..]),
+                Some('S') | Some('s') => ('S', &part[1..]),
+                // Without letters the rule is in the older S/B order
+                _ if i == 0 => ('S', *part),
+                _ => ('B', *part),
+            };
+            let masks = Rule::parse_neighborhoods(digits)?;
+            let slot = if kind == 'B' { &mut birth } else { &mut survival };
+            if slot.is_some() {
+                return Err(format!("rule '{}' has more than one {} part", rule, kind));
+            }
+            *slot = Some(masks);
+        }
+        let states = match parts.get(2) {
+            Some(part) => Rule::parse_states(part)?,
+            None => 2,
+        };
+        let (birth, survival) = match (birth, survival) {
+            (Some(birth), Some(survival)) => (birth, survival),
+            _ => return Err(format!("rule '{}' needs both a B and an S part", rule)),
+        };
+        let totalistic = Rule::is_totalistic(&birth) && Rule::is_totalistic(&survival);
+        let rule = Rule {
+            birth: Rule::totalistic_counts(&birth),
+            survival: Rule::totalistic_counts(&survival),
+            states,
+            range: 1,
+            neighborhood,
+            include_center: false,
+            isotropic: if totalistic { None } else { Some((birth, survival)) },
+            species: 1,
+            cyclic_threshold: None,
+            kernel: None,
+        };
+        if neighborhood != Neighborhood::Moore {
+            if !totalistic {
+                return Err(String::from("Hensel letters only apply to the eight cell neighborhood"));
+            }
+            let max_count = rule.max_count();
+            if let Some(count) = rule.birth.ones().chain(rule.survival.ones()).find(|n| *n > max_count) {
+                return Err(format!("count {} is more than the {} cells in the neighborhood", count, max_count));
+            }
+        }
+        Ok(rule)
+    }
+
+    /// Gets the number of cell states, 2 for plain alive/dead rules.
+    pub fn states(&self) -> u8 {
+        self.states
+    }
+
+    /// Gets how far from a cell its neighbors can be.
+    pub fn range(&self) -> usize {
+        self.range
+    }
+
+    /// Gets the number of colors live cells can have, 1 for single color rules.
+    pub fn species(&self) -> u8 {
+        self.species
+    }
+
+    pub fn neighborhood(&self) -> Neighborhood {
+        self.neighborhood
+    }
+
+    /// Gets which bits of the eight cell neighbor mask of a cell in `row` are its
+    /// neighbors, see `hensel` for the order of the bits.
+    pub fn neighbor_bits(&self, row: usize) -> u8 {
+        match self.neighborhood {
+            Neighborhood::Moore => 0b1111_1111,
+            // North, west, east and south
+            Neighborhood::VonNeumann => 0b0101_1010,
+            // Even rows lose the north and south east corners, odd rows the west ones
+            Neighborhood::Hexagonal if row.is_multiple_of(2) => 0b0111_1011,
+            Neighborhood::Hexagonal => 0b1101_1110,
+        }
+    }
+
+    /// Gets how many neighbors one state ahead of a cell move it on, for cyclic rules.
+    pub fn cyclic_threshold(&self) -> Option<usize> {
+        self.cyclic_threshold
+    }
+
+    /// Gets the kernel of a weighted rule.
+    pub fn kernel(&self) -> Option<&Kernel> {
+        self.kernel.as_ref()
+    }
+
+    /// Gets whether a cell counts itself as one of its live neighbors.
+    pub fn include_center(&self) -> bool {
+        self.include_center
+    }
+
+    /// Gets whether the rule is a plain two state rule counting the eight surrounding cells.
+    pub fn is_life_like(&self) -> bool {
+        self.states == 2 && self.counts_neighbors_only()
+    }
+
+    /// Gets whether the next state of a cell only depends on its state and how many
+    /// of the eight surrounding cells are alive, so the rule can run on any graph.
+    pub fn counts_neighbors_only(&self) -> bool {
+        self.neighborhood == Neighborhood::Moore
+            && !self.is_larger_than_life()
+            && self.isotropic.is_none()
+            && self.species == 1
+            && self.cyclic_threshold.is_none()
+            && self.kernel.is_none()
+    }
+
+    /// Gets whether the rule needs more than the cells right around a cell to compute a generation.
+    /// Cyclic and weighted rules count their own way and are not Larger than Life rules.
+    pub fn is_larger_than_life(&self) -> bool {
+        self.cyclic_threshold.is_none() && self.kernel.is_none() && (self.range > 1 || self.include_center)
+    }
+
+    /// Gets the state of a cell in the next generation given its current
+    /// state and the number of live cells around it.
+    ///
+    /// State 0 is dead, 1 is alive and anything above is a dying cell.
+    pub fn next_state(&self, state: u8, live_neighbors: usize) -> u8 {
+        self.transition(state, self.birth.contains(live_neighbors), self.survival.contains(live_neighbors))
+    }
+
+    /// Gets the state of a cell in the next generation given its current state
+    /// and the 8 bit mask of the live cells around it, see `hensel`.
+    ///
+    /// Isotropic non-totalistic rules need the mask, for every other rule this
+    /// is the same as `next_state` with the number of bits set.
+    pub fn next_state_from_neighbors(&self, state: u8, neighbors: u8) -> u8 {
+        match self.isotropic {
+            Some((ref birth, ref survival)) => {
+                let mask = neighbors as usize;
+                self.transition(state, birth.contains(mask), survival.contains(mask))
+            }
+            None => self.next_state(state, neighbors.count_ones() as usize),
+        }
+    }
+
+    /// Gets the state of a cell in the next generation under a cyclic rule given
+    /// its current state and how many of its neighbors are one state ahead.
+    pub fn next_cyclic_state(&self, state: u8, successors: usize) -> u8 {
+        match self.cyclic_threshold {
+            Some(threshold) if successors >= threshold => self.successor(state),
+            _ => state,
+        }
+    }
+
+    /// Gets the state of a cell in the next generation under a weighted rule given
+    /// its current state and the weighted sum of the live cells around it.
+    pub fn next_weighted_state(&self, state: u8, sum: i32) -> u8 {
+        let min_sum = self.kernel.as_ref().map_or(0, |kernel| kernel.min_sum());
+        let idx = (sum - min_sum) as usize;
+        self.transition(state, self.birth.contains(idx), self.survival.contains(idx))
+    }
+
+    /// Gets the state after a state under a cyclic rule, the last one wrapping to 0.
+    pub fn successor(&self, state: u8) -> u8 {
+        ((state as usize + 1) % self.states as usize) as u8
+    }
+
+    /// Gets the state of a cell in the next generation given its current state
+    /// and whether a dead cell is born or a live cell survives.
+    pub fn transition(&self, state: u8, born: bool, survives: bool) -> u8 {
+        match state {
+            0 if born => 1,
+            0 => 0,
+            1 if survives => 1,
+            s if s + 1 < self.states => s + 1,
+            _ => 0,
+        }
+    }
+
+    /// Parses the neighbor counts of a B or S part into the set of neighborhood masks
+    /// they select. Each count may be followed by Hensel letters to only take some of
+    /// its neighborhoods, e.g. "34q", or by a '-' and the letters to leave out, e.g. "2-a".
+    fn parse_neighborhoods(digits: &str) -> Result<FixedBitSet, String> {
+        let mut masks = FixedBitSet::with_capacity(256);
+        let mut chars = digits.chars().peekable();
+        while let Some(c) = chars.next() {
+            let count = match c.to_digit(10) {
+                Some(n) if n as usize <= MAX_NEIGHBORS => n as usize,
+                _ => return Err(format!("'{}' is not a neighbor count between 0 and {}", c, MAX_NEIGHBORS)),
+            };
+            let negated = chars.next_if_eq(&'-').is_some();
+            let mut letters = String::new();
+            while let Some(letter) = chars.next_if(|l| l.is_ascii_alphabetic()) {
+                letters.push(letter);
+            }
+            let all = hensel::LETTERS[count];
+            if let Some(letter) = letters.chars().find(|l| !all.contains(*l)) {
+                return Err(format!("'{}' is not a Hensel letter for {} neighbors", letter, count));
+            }
+            if negated && letters.is_empty() {
+                return Err(format!("'{}-' needs the letters to leave out", count));
+            }
+            if all.is_empty() {
+                // No or all eight neighbors alive is a single neighborhood without letters
+                masks.insert(if count == 0 { 0 } else { 255 });
+                continue;
+            }
+            for letter in all.chars() {
+                if letters.is_empty() || letters.contains(letter) != negated {
+                    for mask in hensel::neighborhoods(count, letter).unwrap() {
+                        masks.insert(mask as usize);
+                    }
+                }
+            }
+        }
+        Ok(masks)
+    }
+
+    /// Gets whether a set of neighborhoods takes either all or none of each count.
+    fn is_totalistic(masks: &FixedBitSet) -> bool {
+        let counts = Rule::totalistic_counts(masks);
+        (0..256).all(|mask: u32| masks.contains(mask as usize) == counts.contains(mask.count_ones() as usize))
+    }
+
+    /// Gets the neighbor counts whose neighborhoods are all in a set.
+    fn totalistic_counts(masks: &FixedBitSet) -> FixedBitSet {
+        let mut counts = FixedBitSet::with_capacity(MAX_NEIGHBORS + 1);
+        for count in 0..=MAX_NEIGHBORS {
+            if (0..256u32).filter(|mask| mask.count_ones() as usize == count).all(|mask| masks.contains(mask as usize)) {
+                counts.insert(count);
+            }
+        }
+        counts
+    }
+
+    /// Writes the counts of a set of neighborhoods with the Hensel letters they take,
+    /// or the letters they leave out after a '-' when that is shorter.
+    fn fmt_neighborhoods(f: &mut fmt::Formatter, masks: &FixedBitSet) -> fmt::Result {
+        for (count, all) in hensel::LETTERS.iter().enumerate() {
+            if all.is_empty() {
+                if masks.contains(if count == 0 { 0 } else { 255 }) {
+                    write!(f, "{}", count)?;
+                }
+                continue;
+            }
+            let taken: String = all.chars()
+                .filter(|letter| hensel::neighborhoods(count, *letter).unwrap().iter().all(|m| masks.contains(*m as usize)))
+                .collect();
+            if taken.is_empty() {
+                continue;
+            }
+            write!(f, "{}", count)?;
+            if taken.len() == all.len() {
+                continue;
+            }
+            if taken.len() * 2 <= all.len() {
+                write!(f, "{}", taken)?;
+            } else {
+                let left_out: String = all.chars().filter(|letter| !taken.contains(*letter)).collect();
+                write!(f, "-{}", left_out)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Parses the B/S rule after the name of a multi-color rule, Conway's life when
+    /// there is none. Only two state rules on the cells right around a cell can have
+    /// colors.
+    fn parse_species(rest: &str, species: u8) -> Result<Rule, String> {
+        let mut rule = match rest.trim() {
+            "" => Rule::default(),
+            rest => Rule::parse(rest)?,
+        };
+        if rule.states > 2 || rule.is_larger_than_life() || rule.species > 1 || rule.cyclic_threshold.is_some() || rule.kernel.is_some() {
+            return Err(format!("'{}' cannot have colors, only two state range 1 rules can", rest.trim()));
+        }
+        rule.species = species;
+        Ok(rule)
+    }
+
+    /// Parses a Larger than Life rule, e.g. "R5,C0,M1,S34..58,B34..45,NM".
+    ///
+    /// R is the range, C the number of states (0 and 2 both mean alive/dead),
+    /// M whether the middle cell is counted, S and B the survival and birth count
+    /// ranges and N the neighborhood, M for Moore or N for von Neumann.
+    fn parse_larger_than_life(rule: &str) -> Result<Rule, String> {
+        let mut range = None;
+        let mut states = 2;
+        let mut include_center = false;
+        let mut neighborhood = Neighborhood::Moore;
+        let mut birth_ranges = Vec::new();
+        let mut survival_ranges = Vec::new();
+        for part in rule.split(',').map(|p| p.trim()) {
+            let mut chars = part.chars();
+            let kind = chars.next().map(|c| c.to_ascii_uppercase());
+            let value = chars.as_str();
+            match kind {
+                Some('R') => match value.parse::<usize>() {
+                    Ok(r) if (1..=MAX_RANGE).contains(&r) => range = Some(r),
+                    _ => return Err(format!("'{}' is not a range between 1 and {}", part, MAX_RANGE)),
+                },
+                Some('C') => match value.parse::<u8>() {
+                    Ok(0) | Ok(1) => states = 2,
+                    Ok(n) => states = n,
+                    _ => return Err(format!("'{}' is not a state count", part)),
+                },
+                Some('M') => match value {
+                    "0" => include_center = false,
+                    "1" => include_center = true,
+                    _ => return Err(format!("'{}' should be M0 or M1", part)),
+                },
+                Some('S') => survival_ranges.push(Rule::parse_count_range(part, value)?),
+                Some('B') => birth_ranges.push(Rule::parse_count_range(part, value)?),
+                Some('N') => match value {
+                    "M" | "m" => neighborhood = Neighborhood::Moore,
+                    "N" | "n" => neighborhood = Neighborhood::VonNeumann,
+                    _ => return Err(format!("'{}' should be NM or NN", part)),
+                },
+                _ => return Err(format!("'{}' is not part of a Larger than Life rule", part)),
+            }
+        }
+        let range = range.ok_or_else(|| format!("rule '{}' needs a range such as R5", rule))?;
+        if birth_ranges.is_empty() || survival_ranges.is_empty() {
+            return Err(format!("rule '{}' needs both a B and an S range", rule));
+        }
+        let mut rule = Rule {
+            birth: FixedBitSet::new(),
+            survival: FixedBitSet::new(),
+            states,
+            range,
+            neighborhood,
+            include_center,
+            isotropic: None,
+            species: 1,
+            cyclic_threshold: None,
+            kernel: None,
+        };
+        let max_count = rule.max_count();
+        rule.birth = Rule::counts_from_ranges(&birth_ranges, max_count)?;
+        rule.survival = Rule::counts_from_ranges(&survival_ranges, max_count)?;
+        Ok(rule)
+    }
+
+    /// Parses a cyclic rule, e.g. "R1/T3/C3/NM".
+    ///
+    /// R is the range, T the threshold of neighbors one state ahead, C the number
+    /// of states and N the neighborhood, M for Moore or N for von Neumann.
+    fn parse_cyclic(rule: &str) -> Result<Rule, String> {
+        let mut range = None;
+        let mut threshold = None;
+        let mut states = None;
+        let mut neighborhood = Neighborhood::Moore;
+        for part in rule.split('/').map(|p| p.trim()) {
+            let mut chars = part.chars();
+            let kind = chars.next().map(|c| c.to_ascii_uppercase());
+            let value = chars.as_str();
+            match kind {
+                Some('R') => match value.parse::<usize>() {
+                    Ok(r) if (1..=MAX_RANGE).contains(&r) => range = Some(r),
+                    _ => return Err(format!("'{}' is not a range between 1 and {}", part, MAX_RANGE)),
+                },
+                Some('T') => match value.parse::<usize>() {
+                    Ok(t) if t >= 1 => threshold = Some(t),
+                    _ => return Err(format!("'{}' is not a threshold of at least 1", part)),
+                },
+                Some('C') => states = Some(Rule::parse_states(part)?),
+                Some('N') => match value {
+                    "M" | "m" => neighborhood = Neighborhood::Moore,
+                    "N" | "n" => neighborhood = Neighborhood::VonNeumann,
+                    _ => return Err(format!("'{}' should be NM or NN", part)),
+                },
+                _ => return Err(format!("'{}' is not part of a cyclic rule", part)),
+            }
+        }
+        let (range, threshold, states) = match (range, threshold, states) {
+            (Some(range), Some(threshold), Some(states)) => (range, threshold, states),
+            _ => return Err(format!("rule '{}' needs a range, a threshold and a state count such as R1/T3/C3", rule)),
+        };
+        let rule = Rule {
+            birth: FixedBitSet::new(),
+            survival: FixedBitSet::new(),
+            states,
+            range,
+            neighborhood,
+            include_center: false,
+            isotropic: None,
+            species: 1,
+            cyclic_threshold: Some(threshold),
+            kernel: None,
+        };
+        if threshold > rule.max_count() {
+            return Err(format!("threshold {} is more than the {} cells in the neighborhood", threshold, rule.max_count()));
+        }
+        Ok(rule)
+    }
+
+    /// Parses the kernel and the B/S parts after the name of a weighted rule, e.g.
+    /// "1,2,1;2,0,2;1,2,1 B6..8/S4..7/C3".
+    ///
+    /// Kernel rows are separated by ';' or new lines and their weights by ',' or
+    /// spaces, so a kernel can be written out as a square in a text file. B and S
+    /// take sums and ranges of sums separated by ',', e.g. "B6..8,10", and the
+    /// optional C part is the number of states. Anything after a '#' is a comment.
+    fn parse_weighted(text: &str) -> Result<Rule, String> {
+        let mut rows = Vec::new();
+        let mut sums: [Option<Vec<(i32, i32)>>; 2] = [None, None];
+        let mut states = 2;
+        for line in text.split(['\n', ';']) {
+            let line = line.split('#').next().unwrap_or_default();
+            let mut row = Vec::new();
+            for token in line.split_whitespace() {
+                if !token.starts_with(|c: char| c.is_ascii_alphabetic()) {
+                    for weight in token.split(',').filter(|w| !w.is_empty()) {
+                        row.push(weight.parse::<i32>().map_err(|_| format!("'{}' is not a whole number weight", weight))?);
+                    }
+                    continue;
+                }
+                for part in token.split('/').filter(|p| !p.is_empty()) {
+                    let mut chars = part.chars();
+                    let kind = chars.next().map(|c| c.to_ascii_uppercase());
+                    let value = chars.as_str();
+                    match kind {
+                        Some(kind @ ('B' | 'S')) => {
+                            let ranges = value.split(',').filter(|v| !v.is_empty())
+                                .map(|v| Rule::parse_sum_range(part, v))
+                                .collect::<Result<Vec<(i32, i32)>, String>>()?;
+                            sums[(kind == 'S') as usize] = Some(ranges);
+                        }
+                        Some('C') => states = Rule::parse_states(part)?,
+                        _ => return Err(format!("'{}' is not a B, S or C part of a weighted rule", part)),
+                    }
+                }
+            }
+            if !row.is_empty() {
+                rows.push(row);
+            }
+        }
+        let kernel = Kernel::from_rows(&rows)?;
+        let [Some(birth), Some(survival)] = sums else {
+            return Err(String::from("a weighted rule needs both a B and an S part"));
+        };
+        let (min_sum, max_sum) = (kernel.min_sum(), kernel.max_sum());
+        let sum_set = |ranges: &[(i32, i32)]| {
+            let mut set = FixedBitSet::with_capacity((max_sum - min_sum) as usize + 1);
+            for (min, max) in ranges {
+                if *min < min_sum || *max > max_sum {
+                    return Err(format!("sums {}..{} are not all between {} and {}, the sums the kernel can give", min, max, min_sum, max_sum));
+                }
+                set.insert_range((min - min_sum) as usize..(max - min_sum) as usize + 1);
+            }
+            Ok(set)
+        };
+        Ok(Rule {
+            birth: sum_set(&birth)?,
+            survival: sum_set(&survival)?,
+            states,
+            range: kernel.radius().max(1),
+            neighborhood: Neighborhood::Moore,
+            include_center: false,
+            isotropic: None,
+            species: 1,
+            cyclic_threshold: None,
+            kernel: Some(kernel),
+        })
+    }
+
+    fn parse_sum_range(part: &str, value: &str) -> Result<(i32, i32), String> {
+        let bounds = match value.split_once("..") {
+            Some((min, max)) => (min.parse::<i32>(), max.parse::<i32>()),
+            None => (value.parse::<i32>(), value.parse::<i32>()),
+        };
+        match bounds {
+            (Ok(min), Ok(max)) if min <= max => Ok((min, max)),
+            _ => Err(format!("'{}' in '{}' is not a sum or a range of sums such as 6..8", value, part)),
+        }
+    }
+
+    /// Writes the weighted sums of a birth or survival set as runs such as "6..8,10".
+    fn fmt_sums(f: &mut fmt::Formatter, sums: &FixedBitSet, min_sum: i32) -> fmt::Result {
+        let mut ones = sums.ones().peekable();
+        let mut first = true;
+        while let Some(min) = ones.next() {
+            let mut max = min;
+            while ones.peek() == Some(&(max + 1)) {
+                max = ones.next().unwrap();
+            }
+            let separator = if first { "" } else { "," };
+            first = false;
+            let (min, max) = (min as i32 + min_sum, max as i32 + min_sum);
+            if min == max {
+                write!(f, "{}{}", separator, min)?;
+            } else {
+                write!(f, "{}{}..{}", separator, min, max)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Gets the largest number of live cells a neighborhood can hold.
+    fn max_count(&self) -> usize {
+        let r = self.range;
+        let cells = match self.neighborhood {
+            Neighborhood::Moore => (2 * r + 1) * (2 * r + 1),
+            Neighborhood::VonNeumann => 2 * r * (r + 1) + 1,
+            Neighborhood::Hexagonal => 3 * r * (r + 1) + 1,
+        };
+        if self.include_center { cells } else { cells - 1 }
+    }
+
+    fn parse_count_range(part: &str, value: &str) -> Result<(usize, usize), String> {
+        let bounds = match value.split_once("..") {
+            Some((min, max)) => (min.parse::<usize>(), max.parse::<usize>()),
+            None => (value.parse::<usize>(), value.parse::<usize>()),
+        };
+        match bounds {
+            (Ok(min), Ok(max)) if min <= max => Ok((min, max)),
+            _ => Err(format!("'{}' is not a count range such as S34..58", part)),
+        }
+    }
+
+    fn counts_from_ranges(ranges: &[(usize, usize)], max_count: usize) -> Result<FixedBitSet, String> {
+        let mut counts = FixedBitSet::with_capacity(max_count + 1);
+        for (min, max) in ranges {
+            if *max > max_count {
+                return Err(format!("count {} is more than the {} cells in the neighborhood", max, max_count));
+            }
+            counts.insert_range(*min..*max + 1);
+        }
+        Ok(counts)
+    }
+
+    fn parse_states(part: &str) -> Result<u8, String> {
+        let digits = part.strip_prefix('C').or_else(|| part.strip_prefix('c')).unwrap_or(part);
+        match digits.parse::<u8>() {
+            Ok(n) if (2..=MAX_STATES).contains(&n) => Ok(n),
+            _ => Err(format!("'{}' is not a state count between 2 and {}", part, MAX_STATES)),
+        }
+    }
+
+    fn fmt_larger_than_life(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let states = if self.states > 2 { self.states } else { 0 };
+        write!(f, "R{},C{},M{}", self.range, states, self.include_center as u8)?;
+        Rule::fmt_count_ranges(f, 'S', &self.survival)?;
+        Rule::fmt_count_ranges(f, 'B', &self.birth)?;
+        match self.neighborhood {
+            Neighborhood::VonNeumann => write!(f, ",NN"),
+            _ => write!(f, ",NM"),
+        }
+    }
+
+    /// Writes each run of consecutive counts as its own "S34..58" style range.
+    fn fmt_count_ranges(f: &mut fmt::Formatter, kind: char, counts: &FixedBitSet) -> fmt::Result {
+        let mut ones = counts.ones().peekable();
+        while let Some(min) = ones.next() {
+            let mut max = min;
+            while ones.peek() == Some(&(max + 1)) {
+                max = ones.next().unwrap();
+            }
+            write!(f, ",{}{}..{}", kind, min, max)?;
+        }
+        Ok(())
+    }
+}
+
+impl Default for Rule {
+    /// Conway's game of life, B3/S23.
+    fn default() -> Rule {
+        Rule::parse("B3/S23").unwrap()
+    }
+}
+
+impl fmt::Display for Rule {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some((name, _)) = SPECIES_RULES.iter().find(|(_, species)| *species == self.species) {
+            let single_color = Rule { species: 1, ..self.clone() };
+            return if single_color == Rule::default() {
+                write!(f, "{}", name)
+            } else {
+                write!(f, "{} {}", name, single_color)
+            };
+        }
+        if let Some(ref kernel) = self.kernel {
+            write!(f, "{} {} B", WEIGHTED_NAME, kernel)?;
+            Rule::fmt_sums(f, &self.birth, kernel.min_sum())?;
+            write!(f, "/S")?;
+            Rule::fmt_sums(f, &self.survival, kernel.min_sum())?;
+            if self.states > 2 {
+                write!(f, "/C{}", self.states)?;
+            }
+            return Ok(());
+        }
+        if let Some(threshold) = self.cyclic_threshold {
+            let neighborhood = if self.neighborhood == Neighborhood::VonNeumann { 'N' } else { 'M' };
+            return write!(f, "R{}/T{}/C{}/N{}", self.range, threshold, self.states, neighborhood);
+        }
+        if self.is_larger_than_life() {
+            return self.fmt_larger_than_life(f);
+        }
+        if let Some((ref birth, ref survival)) = self.isotropic {
+            write!(f, "B")?;
+            Rule::fmt_neighborhoods(f, birth)?;
+            write!(f, "/S")?;
+            Rule::fmt_neighborhoods(f, survival)?;
+        } else {
+            write!(f, "B")?;
+            for n in self.birth.ones() {
+                write!(f, "{}", n)?;
+            }
+            write!(f, "/S")?;
+            for n in self.survival.ones() {
+                write!(f, "{}", n)?;
+            }
+        }
+        if self.states > 2 {
+            write!(f, "/C{}", self.states)?;
+        }
+        match self.neighborhood {
+            Neighborhood::Hexagonal => write!(f, "H"),
+            Neighborhood::VonNeumann => write!(f, "V"),
+            Neighborhood::Moore => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Rule;
+
+    #[test]
+    fn parses_common_rules() {
+        for rule in ["B3/S23", "B36/S23", "B2/S", "B3678/S34678", "B2/S/C3", "B2/S345/C4"] {
+            assert_eq!(Rule::parse(rule).unwrap().to_string(), rule);
+        }
+    }
+
+    #[test]
+    fn parses_alternate_forms() {
+        assert_eq!(Rule::parse("s23/b3").unwrap(), Rule::default());
+        assert_eq!(Rule::parse("23/3").unwrap(), Rule::default());
+        assert_eq!(Rule::parse("B2/S345/4").unwrap().states(), 4);
+    }
+
+    #[test]
+    fn parses_larger_than_life_rules() {
+        let bosco = Rule::parse("R5,C0,M1,S34..58,B34..45,NM").unwrap();
+        assert_eq!(bosco.range(), 5);
+        assert!(bosco.include_center());
+        assert_eq!(bosco.to_string(), "R5,C0,M1,S34..58,B34..45,NM");
+        let diamond = Rule::parse("R3,C4,M0,S2..4,S8..9,B3..3,NN").unwrap();
+        assert_eq!(diamond.neighborhood(), super::Neighborhood::VonNeumann);
+        assert_eq!(diamond.states(), 4);
+        assert_eq!(diamond.to_string(), "R3,C4,M0,S2..4,S8..9,B3..3,NN");
+        assert!(Rule::parse("R1,C0,M0,S2..9,B3..3,NM").is_err());
+        assert!(Rule::parse("R11,C0,M0,S2..3,B3..3,NM").is_err());
+    }
+
+    #[test]
+    fn parses_cyclic_rules() {
+        let rule = Rule::parse("r1/t3/c3/nm").unwrap();
+        assert_eq!(rule.to_string(), "R1/T3/C3/NM");
+        assert_eq!(rule.cyclic_threshold(), Some(3));
+        assert!(!rule.is_larger_than_life() && !rule.is_life_like());
+        assert_eq!(Rule::parse("R2/T5/C14/NN").unwrap().to_string(), "R2/T5/C14/NN");
+        assert_eq!(rule.next_cyclic_state(2, 3), 0);
+        assert_eq!(rule.next_cyclic_state(1, 2), 1);
+        assert!(Rule::parse("R1/T9/C3/NM").is_err());
+        assert!(Rule::parse("R1/T1").is_err());
+        assert!(Rule::parse("QuadLife R1/T1/C4/NM").is_err());
+    }
+
+    #[test]
+    fn parses_weighted_rules() {
+        let text = "Weighted\n# Closer cells count double\n1 2 1\n2 0 2\n1 2 1\nB6..8,10 S4..7\n";
+        let rule = Rule::parse(text).unwrap();
+        assert_eq!(rule.to_string(), "Weighted 1,2,1;2,0,2;1,2,1 B6..8,10/S4..7");
+        assert_eq!(Rule::parse(&rule.to_string()).unwrap(), rule);
+        assert!(!rule.is_larger_than_life() && !rule.counts_neighbors_only());
+        assert_eq!(rule.next_weighted_state(0, 10), 1);
+        assert_eq!(rule.next_weighted_state(0, 9), 0);
+        assert_eq!(rule.next_weighted_state(1, 4), 1);
+        let inhibited = Rule::parse("weighted 0,1,0;-1,0,1;0,1,0 B-1..0/S/C3").unwrap();
+        assert_eq!(inhibited.to_string(), "Weighted 0,1,0;-1,0,1;0,1,0 B-1..0/S/C3");
+        assert_eq!(inhibited.next_weighted_state(0, -1), 1);
+        assert_eq!(inhibited.next_weighted_state(1, 0), 2);
+        assert!(Rule::parse("Weighted 1,1;1,1 B2/S2").is_err());
+        assert!(Rule::parse("Weighted 1,1,1;1,0,1;1,1,1 B9/S2").is_err());
+        assert!(Rule::parse("Weighted 1,1,1;1,0,1;1,1,1 B3").is_err());
+        assert!(Rule::parse("QuadLife Weighted 1,1,1;1,0,1;1,1,1 B3/S23").is_err());
+        assert!(Rule::parse("Weighted 1,1,1;1,0,1;1,1,1 B3/éx").is_err());
+    }
+
+    #[test]
+    fn rejects_bad_rules() {
+        assert!(Rule::parse("B3").is_err());
+        assert!(Rule::parse("B39/S23").is_err());
+        assert!(Rule::parse("B3/B23").is_err());
+        assert!(Rule::parse("B2/S/C1").is_err());
+    }
+
+    #[test]
+    fn conway_transitions() {
+        let rule = Rule::default();
+        assert_eq!(rule.next_state(0, 3), 1);
+        assert_eq!(rule.next_state(0, 2), 0);
+        assert_eq!(rule.next_state(1, 2), 1);
+        assert_eq!(rule.next_state(1, 4), 0);
+    }
+
+    #[test]
+    fn parses_isotropic_rules() {
+        for rule in ["B2-a/S12", "B3/S2-i34q", "B2ce3aiy/S23-a4k/C3", "B0/S8", "B1e/S"] {
+            assert_eq!(Rule::parse(rule).unwrap().to_string(), rule);
+        }
+        // Every letter of a count is the same as the plain count
+        assert_eq!(Rule::parse("B3ceaiknjqry/S23").unwrap(), Rule::default());
+        assert!(Rule::parse("B2-/S23").is_err());
+        assert!(Rule::parse("B2x/S23").is_err());
+        assert!(Rule::parse("B4t/S1t").is_err());
+    }
+
+    #[test]
+    fn isotropic_transitions() {
+        let tlife = Rule::parse("B3/S2-i34q").unwrap();
+        assert!(!tlife.is_life_like());
+        // Two neighbors north and south of a live cell are 2i, left out of survival
+        assert_eq!(tlife.next_state_from_neighbors(1, 0b0100_0010), 0);
+        // North and east are 2e
+        assert_eq!(tlife.next_state_from_neighbors(1, 0b0001_0010), 1);
+        assert_eq!(tlife.next_state_from_neighbors(0, 0b0000_0111), 1);
+        // A totalistic rule only looks at the count
+        let life = Rule::default();
+        assert_eq!(life.next_state_from_neighbors(1, 0b0100_0010), 1);
+    }
+
+    #[test]
+    fn parses_neighborhood_suffixes() {
+        for rule in ["B2/S34H", "B1/S1V", "B2/S/C3H", "B13/S012V"] {
+            assert_eq!(Rule::parse(rule).unwrap().to_string(), rule);
+        }
+        assert_eq!(Rule::parse("B2/S34H").unwrap().neighborhood(), super::Neighborhood::Hexagonal);
+        assert_eq!(Rule::parse("R1,C0,M0,S1..1,B1..1,NN").unwrap().to_string(), "B1/S1V");
+        assert!(Rule::parse("B7/S34H").is_err());
+        assert!(Rule::parse("B1/S5V").is_err());
+        assert!(Rule::parse("B2a/S34H").is_err());
+    }
+
+    #[test]
+    fn parses_species_rules() {
+        for rule in ["Immigration", "QuadLife", "QuadLife B36/S23", "Immigration B2/S34H"] {
+            assert_eq!(Rule::parse(rule).unwrap().to_string(), rule);
+        }
+        assert_eq!(Rule::parse("quadlife").unwrap().species(), 4);
+        assert!(!Rule::parse("Immigration").unwrap().is_life_like());
+        assert!(Rule::parse("Immigration B2/S/C3").is_err());
+        assert!(Rule::parse("QuadLife R2,C0,M0,S3..5,B4..5,NM").is_err());
+        assert!(Rule::parse("QuadLife Immigration").is_err());
+    }
+
+    #[test]
+    fn generations_cells_decay() {
+        let rule = Rule::parse("B2/S/C3").unwrap();
+        assert_eq!(rule.next_state(0, 2), 1);
+        assert_eq!(rule.next_state(1, 2), 2);
+        assert_eq!(rule.next_state(2, 2), 0);
+    }
+}
@@ -0,0 +1,438 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::simulation;
+
+const MAX_STATES: usize = 255;
+
+/// Wireworld for circuit demos: 1 is an electron head, 2 its tail and 3 a conductor.
+pub const WIREWORLD: &str = "
+@RULE WireWorld
+
+@TABLE
+n_states:4
+neighborhood:Moore
+symmetries:permute
+var a={0,1,2,3}
+var b={0,1,2,3}
+var c={0,1,2,3}
+var d={0,1,2,3}
+var e={0,1,2,3}
+var f={0,1,2,3}
+var g={0,1,2,3}
+var h={0,1,2,3}
+var i={0,2,3}
+var j={0,2,3}
+var k={0,2,3}
+var l={0,2,3}
+var m={0,2,3}
+var n={0,2,3}
+var o={0,2,3}
+# Heads turn into tails and tails back into conductor
+1,a,b,c,d,e,f,g,h,2
+2,a,b,c,d,e,f,g,h,3
+# Conductor next to one or two heads becomes a head
+3,1,i,j,k,l,m,n,o,1
+3,1,1,i,j,k,l,m,n,1
+
+@COLORS
+0 48 48 48
+1 0 128 255
+2 255 255 255
+3 255 128 0
+";
+
+/// The states a transition allows for the cell and each neighbor, and the state it leads to.
+type Transition = (Vec<Vec<u8>>, u8);
+
+/// A state or a variable standing for a set of states in a transition.
+#[derive(Clone, Debug)]
+enum Token {
+    State(u8),
+    Var(String, Vec<u8>),
+}
+
+/// A multi-state rule compiled from the @TABLE and @COLORS sections of a Golly `.rule` file.
+///
+/// Every transition lists the states it allows for the cell and each neighbor,
+/// after binding repeated variables and applying the symmetries. For each
+/// position and state a bit set marks the transitions allowing that state
+/// there, so finding the first matching transition is a few word-wide ANDs.
+/// A cell no transition matches keeps its state.
+#[derive(Clone, Debug)]
+pub struct RuleTable {
+    name: String,
+    states: u8,
+    // Row and column offset of each neighbor in the order transitions list them
+    neighbors: Vec<(isize, isize)>,
+    // Bit i of matches[position][state] is set when transition i allows the state
+    // at that position, position 0 being the cell itself
+    matches: Vec<Vec<Vec<u64>>>,
+    outputs: Vec<u8>,
+    colors: Vec<[f32; 3]>,
+}
+
+impl RuleTable {
+
+    /// Parses the text of a `.rule` file. Sections other than @RULE, @TABLE and @COLORS are ignored.
+    pub fn parse(text: &str) -> Result<RuleTable, String> {
+        let mut name = String::from("Table");
+        let mut section = "";
+        let mut states: Option<usize> = None;
+        let mut neighbors: Option<Vec<(isize, isize)>> = None;
+        let mut symmetries = String::from("none");
+        let mut vars: HashMap<String, Vec<u8>> = HashMap::new();
+        let mut transitions: Vec<Vec<Token>> = Vec::new();
+        let mut color_lines: Vec<Vec<u8>> = Vec::new();
+        let mut has_table = false;
+        for line in text.lines() {
+            let line = line.split('#').next().unwrap().trim();
+            if line.is_empty() {
+                continue;
+            }
+            if let Some(header) = line.strip_prefix('@') {
+                let mut words = header.split_whitespace();
+                section = words.next().unwrap_or("");
+                match section {
+                    "RULE" => name = words.next().unwrap_or("Table").to_string(),
+                    "TABLE" => has_table = true,
+                    _ => {}
+                }
+                continue;
+            }
+            match section {
+                "TABLE" => {
+                    if let Some((key, value)) = line.split_once(':') {
+                        let value = value.trim();
+                        match key.trim() {
+                            "n_states" => states = Some(RuleTable::parse_state_count(value)?),
+                            "neighborhood" => neighbors = Some(RuleTable::parse_neighborhood(value)?),
+                            "symmetries" => symmetries = value.to_string(),
+                            key => return Err(format!("'{}' is not a table setting", key)),
+                        }
+                    } else if let Some(var) = line.strip_prefix("var ") {
+                        let states = states.ok_or("n_states must come before the variables")?;
+                        let (var_name, values) = var.split_once('=').ok_or_else(|| format!("'{}' should be var name={{...}}", line))?;
+                        let values = values.trim().trim_start_matches('{').trim_end_matches('}');
+                        let mut set = Vec::new();
+                        for value in values.split(',') {
+                            match RuleTable::parse_token(value.trim(), &vars, states)? {
+                                Token::State(state) => set.push(state),
+                                Token::Var(_, values) => set.extend(values),
+                            }
+                        }
+                        vars.insert(var_name.trim().to_string(), set);
+                    } else {
+                        let states = states.ok_or("n_states must come before the transitions")?;
+                        let values: Vec<String> = if line.contains(',') {
+                            line.split(',').map(|v| v.trim().to_string()).collect()
+                        } else {
+                            // Without commas every character is a state or a one letter variable
+                            line.chars().filter(|c| !c.is_whitespace()).map(|c| c.to_string()).collect()
+                        };
+                        let tokens = values.iter()
+                            .map(|value| RuleTable::parse_token(value, &vars, states))
+                            .collect::<Result<Vec<Token>, String>>()?;
+                        transitions.push(tokens);
+                    }
+                }
+                "COLORS" => {
+                    let values = line.split_whitespace()
+                        .map(|v| v.parse::<u8>().map_err(|_| format!("'{}' is not a color value", v)))
+                        .collect::<Result<Vec<u8>, String>>()?;
+                    color_lines.push(values);
+                }
+                _ => {}
+            }
+        }
+        if !has_table {
+            return Err(String::from("only rules with a @TABLE section are supported"));
+        }
+        let states = states.ok_or("the table needs n_states")?;
+        let neighbors = neighbors.ok_or("the table needs a neighborhood")?;
+        let permutations = RuleTable::symmetry_permutations(&symmetries, neighbors.len())?;
+
+        let mut table = RuleTable {
+            name,
+            states: states as u8,
+            neighbors,
+            matches: Vec::new(),
+            outputs: Vec::new(),
+            colors: simulation::default_palette(states as u8),
+        };
+        let mut seen = HashSet::new();
+        let mut compiled: Vec<Transition> = Vec::new();
+        for tokens in transitions {
+            if tokens.len() != table.neighbors.len() + 2 {
+                return Err(format!("a transition should have {} states", table.neighbors.len() + 2));
+            }
+            for (inputs, output) in RuleTable::bind_variables(&tokens)? {
+                let (center, around) = inputs.split_first().unwrap();
+                let arrangements: Vec<Vec<Vec<u8>>> = match permutations {
+                    Some(ref permutations) => permutations.iter()
+                        .map(|permutation| permutation.iter().map(|i| around[*i].clone()).collect())
+                        .collect(),
+                    None => RuleTable::distinct_arrangements(around),
+                };
+                for around in arrangements {
+                    let mut inputs = vec![center.clone()];
+                    inputs.extend(around);
+                    if seen.insert((inputs.clone(), output)) {
+                        compiled.push((inputs, output));
+                    }
+                }
+            }
+        }
+        table.compile(&compiled);
+        table.apply_colors(&color_lines)?;
+        Ok(table)
+    }
+
+    /// Gets the name from the @RULE line.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn states(&self) -> u8 {
+        self.states
+    }
+
+    /// Gets the row and column offsets of the neighbors, in the order `next_state` takes them.
+    pub fn neighbors(&self) -> &[(isize, isize)] {
+        &self.neighbors
+    }
+
+    /// Gets the color of every state from @COLORS, state 0 first.
+    pub fn colors(&self) -> &[[f32; 3]] {
+        &self.colors
+    }
+
+    /// Gets the next state of a cell from its own state followed by the states of its neighbors.
+    pub fn next_state(&self, states: &[u8]) -> u8 {
+        for word in 0..self.outputs.len().div_ceil(64) {
+            let mut matching = u64::MAX;
+            for (position, state) in states.iter().enumerate() {
+                matching &= self.matches[position][*state as usize][word];
+                if matching == 0 {
+                    break;
+                }
+            }
+            if matching != 0 {
+                return self.outputs[word * 64 + matching.trailing_zeros() as usize];
+            }
+        }
+        states[0]
+    }
+
+    fn parse_state_count(value: &str) -> Result<usize, String> {
+        match value.parse::<usize>() {
+            Ok(n) if (2..=MAX_STATES).contains(&n) => Ok(n),
+            _ => Err(format!("'{}' is not a state count between 2 and {}", value, MAX_STATES)),
+        }
+    }
+
+    /// Gets the neighbor offsets of a neighborhood, clockwise from north as Golly lists them.
+    fn parse_neighborhood(value: &str) -> Result<Vec<(isize, isize)>, String> {
+        match value {
+            "Moore" => Ok(vec![(-1, 0), (-1, 1), (0, 1), (1, 1), (1, 0), (1, -1), (0, -1), (-1, -1)]),
+            "vonNeumann" => Ok(vec![(-1, 0), (0, 1), (1, 0), (0, -1)]),
+            _ => Err(format!("the '{}' neighborhood is not supported, only Moore and vonNeumann", value)),
+        }
+    }
+
+    fn parse_token(value: &str, vars: &HashMap<String, Vec<u8>>, states: usize) -> Result<Token, String> {
+        if let Some(values) = vars.get(value) {
+            return Ok(Token::Var(value.to_string(), values.clone()));
+        }
+        match value.parse::<usize>() {
+            Ok(state) if state < states => Ok(Token::State(state as u8)),
+            _ => Err(format!("'{}' is neither a state below {} nor a variable", value, states)),
+        }
+    }
+
+    /// Expands the variables used more than once in a transition, which must take the same
+    /// value everywhere, into one transition per value. Variables used once match any of
+    /// their states. Gets the allowed states of each input and the output state.
+    fn bind_variables(tokens: &[Token]) -> Result<Vec<Transition>, String> {
+        let mut uses: HashMap<&str, usize> = HashMap::new();
+        for token in tokens {
+            if let Token::Var(name, _) = token {
+                *uses.entry(name).or_default() += 1;
+            }
+        }
+        let mut bindings: Vec<HashMap<&str, u8>> = vec![HashMap::new()];
+        for token in tokens {
+            if let Token::Var(name, values) = token {
+                if uses[name.as_str()] > 1 && !bindings[0].contains_key(name.as_str()) {
+                    bindings = bindings.into_iter()
+                        .flat_map(|binding| values.iter().map(move |value| {
+                            let mut binding = binding.clone();
+                            binding.insert(name, *value);
+                            binding
+                        }))
+                        .collect();
+                }
+            }
+        }
+        let (output, inputs) = tokens.split_last().unwrap();
+        bindings.iter().map(|binding| {
+            let allowed = inputs.iter().map(|token| match token {
+                Token::State(state) => vec![*state],
+                Token::Var(name, values) => match binding.get(name.as_str()) {
+                    Some(value) => vec![*value],
+                    None => values.clone(),
+                },
+            }).collect();
+            let output = match output {
+                Token::State(state) => *state,
+                Token::Var(name, _) => *binding.get(name.as_str())
+                    .ok_or_else(|| format!("the output variable '{}' must also be an input", name))?,
+            };
+            Ok((allowed, output))
+        }).collect()
+    }
+
+    /// Gets how the neighbors can be rearranged under a symmetry, `None` for any order.
+    fn symmetry_permutations(symmetries: &str, count: usize) -> Result<Option<Vec<Vec<usize>>>, String> {
+        let rotate = |step: usize| -> Vec<Vec<usize>> {
+            (0..count).step_by(step).map(|r| (0..count).map(|i| (i + r) % count).collect()).collect()
+        };
+        let with_reflections = |rotations: Vec<Vec<usize>>| -> Vec<Vec<usize>> {
+            let mut all = rotations.clone();
+            // Mirroring left to right keeps north and south and swaps east and west
+            all.extend(rotations.iter().map(|p| (0..count).map(|i| p[(count - i) % count]).collect()));
+            all
+        };
+        let permutations = match symmetries {
+            "none" => vec![(0..count).collect()],
+            "rotate4" => rotate(count / 4),
+            "rotate8" if count == 8 => rotate(1),
+            "reflect_horizontal" => with_reflections(vec![(0..count).collect()]),
+            "rotate4reflect" => with_reflections(rotate(count / 4)),
+            "rotate8reflect" if count == 8 => with_reflections(rotate(1)),
+            "permute" => return Ok(None),
+            _ => return Err(format!("the '{}' symmetry is not supported for this neighborhood", symmetries)),
+        };
+        Ok(Some(permutations))
+    }
+
+    /// Gets every distinct order of a list of state sets.
+    fn distinct_arrangements(sets: &[Vec<u8>]) -> Vec<Vec<Vec<u8>>> {
+        let mut order: Vec<Vec<u8>> = sets.to_vec();
+        order.sort();
+        let mut arrangements = vec![order.clone()];
+        // Step through the lexicographic permutations, which skips repeats
+        loop {
+            let Some(i) = (1..order.len()).rev().find(|i| order[i - 1] < order[*i]) else {
+                return arrangements;
+            };
+            let j = (i..order.len()).rev().find(|j| order[*j] > order[i - 1]).unwrap();
+            order.swap(i - 1, j);
+            order[i..].reverse();
+            arrangements.push(order.clone());
+        }
+    }
+
+    fn compile(&mut self, transitions: &[Transition]) {
+        let words = transitions.len().div_ceil(64);
+        let states = self.states as usize;
+        self.matches = vec![vec![vec![0; words]; states]; self.neighbors.len() + 1];
+        self.outputs = Vec::with_capacity(transitions.len());
+        for (i, (inputs, output)) in transitions.iter().enumerate() {
+            for (position, allowed) in inputs.iter().enumerate() {
+                for state in allowed {
+                    self.matches[position][*state as usize][i / 64] |= 1 << (i % 64);
+                }
+            }
+            self.outputs.push(*output);
+        }
+    }
+
+    /// Reads "state r g b" lines, or "r g b r g b" lines fading from state 1 to the last state.
+    fn apply_colors(&mut self, lines: &[Vec<u8>]) -> Result<(), String> {
+        let color = |rgb: &[u8]| [rgb[0] as f32 / 255.0, rgb[1] as f32 / 255.0, rgb[2] as f32 / 255.0];
+        for values in lines {
+            match values.len() {
+                4 if (values[0] as usize) < self.colors.len() => {
+                    self.colors[values[0] as usize] = color(&values[1..]);
+                }
+                6 => {
+                    let (from, to) = (color(&values[..3]), color(&values[3..]));
+                    let last = self.colors.len() - 1;
+                    for state in 1..=last {
+                        let t = if last > 1 { (state - 1) as f32 / (last - 1) as f32 } else { 0.0 };
+                        self.colors[state] = [0, 1, 2].map(|c| from[c] + (to[c] - from[c]) * t);
+                    }
+                }
+                _ => return Err(format!("'{:?}' is not a state and color or a color gradient", values)),
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{RuleTable, WIREWORLD};
+
+    #[test]
+    fn wireworld_transitions() {
+        let table = RuleTable::parse(WIREWORLD).unwrap();
+        assert_eq!(table.name(), "WireWorld");
+        assert_eq!(table.states(), 4);
+        assert_eq!(table.next_state(&[1, 0, 0, 3, 3, 2, 0, 0, 0]), 2);
+        assert_eq!(table.next_state(&[2, 0, 0, 3, 3, 1, 0, 0, 0]), 3);
+        assert_eq!(table.next_state(&[3, 0, 0, 0, 0, 0, 0, 1, 0]), 1);
+        assert_eq!(table.next_state(&[3, 0, 1, 0, 0, 0, 0, 1, 0]), 1);
+        assert_eq!(table.next_state(&[3, 1, 1, 1, 0, 0, 0, 0, 0]), 3);
+        assert_eq!(table.next_state(&[0, 1, 1, 1, 0, 0, 0, 0, 0]), 0);
+        assert_eq!(table.colors()[3], [1.0, 128.0 / 255.0, 0.0]);
+    }
+
+    #[test]
+    fn symmetries_and_bound_variables() {
+        let text = "
+@RULE Test
+@TABLE
+n_states:3
+neighborhood:vonNeumann
+symmetries:rotate4
+var a={1,2}
+# A cell copies the state of a matching pair of neighbors north and east
+0,a,a,0,0,a
+";
+        let table = RuleTable::parse(text).unwrap();
+        assert_eq!(table.next_state(&[0, 2, 2, 0, 0]), 2);
+        // Rotated a quarter turn the pair sits east and south
+        assert_eq!(table.next_state(&[0, 0, 1, 1, 0]), 1);
+        // The pair has to match and be next to each other
+        assert_eq!(table.next_state(&[0, 1, 2, 0, 0]), 0);
+        assert_eq!(table.next_state(&[0, 1, 0, 1, 0]), 0);
+    }
+
+    #[test]
+    fn compact_transitions_and_gradient_colors() {
+        let text = "
+@RULE Compact
+@TABLE
+n_states:3
+neighborhood:vonNeumann
+symmetries:none
+010002
+@COLORS
+0 0 0 255 255 255
+";
+        let table = RuleTable::parse(text).unwrap();
+        assert_eq!(table.next_state(&[0, 1, 0, 0, 0]), 2);
+        assert_eq!(table.next_state(&[0, 0, 1, 0, 0]), 0);
+        assert_eq!(table.colors()[1], [0.0, 0.0, 0.0]);
+        assert_eq!(table.colors()[2], [1.0, 1.0, 1.0]);
+    }
+
+    #[test]
+    fn rejects_bad_tables() {
+        assert!(RuleTable::parse("@RULE Tree\n@TREE\nnum_states=2").is_err());
+        assert!(RuleTable::parse("@TABLE\nn_states:2\nneighborhood:Moore\n0,1,2").is_err());
+        assert!(RuleTable::parse("@TABLE\nn_states:2\nneighborhood:hexagonal\n").is_err());
+        assert!(RuleTable::parse("@TABLE\nn_states:2\nneighborhood:vonNeumann\nvar a={0,1}\n0,1,0,0,0,a").is_err());
+    }
+}
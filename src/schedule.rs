@@ -0,0 +1,108 @@
+use std::fmt;
+
+use crate::random;
+
+const ORDER_STREAM: u64 = 3;
+const ALPHA_STREAM: u64 = 4;
+
+/// The order cells are updated in within a generation, written like "sync",
+/// "sweep", "random seed=3" or "alpha=0.5 seed=3".
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Schedule {
+    /// Every cell moves on at once from the previous generation.
+    #[default]
+    Synchronous,
+    /// Cells are updated in place one at a time, row by row, so later cells see
+    /// the new states of earlier ones.
+    Sweep,
+    /// As many cells as the universe has are picked at random, with repeats, and
+    /// updated in place one at a time.
+    RandomSequential { seed: u64 },
+    /// Each cell moves on from the previous generation with probability `alpha`
+    /// and keeps its state otherwise.
+    AlphaAsynchronous { alpha: f64, seed: u64 },
+}
+
+impl Schedule {
+
+    /// Parses a scheme name or "alpha=p", followed by an optional "seed=n".
+    pub fn parse(text: &str) -> Result<Schedule, String> {
+        let mut schedule = Schedule::Synchronous;
+        let mut seed = 0;
+        for setting in text.split([' ', ',']).filter(|s| !s.is_empty()) {
+            let (key, value) = setting.split_once('=').unwrap_or((setting, ""));
+            schedule = match key.to_ascii_lowercase().as_str() {
+                "seed" => {
+                    seed = value.parse().map_err(|_| format!("'{}' is not a whole number seed", setting))?;
+                    schedule
+                }
+                "sync" if value.is_empty() => Schedule::Synchronous,
+                "sweep" if value.is_empty() => Schedule::Sweep,
+                "random" if value.is_empty() => Schedule::RandomSequential { seed },
+                "alpha" => match value.parse::<f64>() {
+                    Ok(alpha) if alpha > 0.0 && alpha <= 1.0 => Schedule::AlphaAsynchronous { alpha, seed },
+                    _ => return Err(format!("'{}' should have an alpha above 0 and at most 1", setting)),
+                },
+                _ => return Err(format!("'{}' is not sync, sweep, random, alpha=p or seed=n", setting)),
+            };
+        }
+        // The seed may come before or after the scheme
+        if let Schedule::RandomSequential { seed: s } | Schedule::AlphaAsynchronous { seed: s, .. } = &mut schedule {
+            *s = seed;
+        }
+        Ok(schedule)
+    }
+
+    /// Gets the cells to update one at a time in place, in order, or `None`
+    /// when every cell moves on from the previous generation.
+    pub fn in_place_order(&self, cells: usize, generation: u64) -> Option<Vec<usize>> {
+        match self {
+            Schedule::Sweep => Some((0..cells).collect()),
+            Schedule::RandomSequential { seed } => Some((0..cells).map(|pick| {
+                let idx = (random::unit(*seed, generation, pick, ORDER_STREAM) * cells as f64) as usize;
+                idx.min(cells - 1)
+            }).collect()),
+            _ => None,
+        }
+    }
+
+    /// Gets whether a cell moves on this generation, always true but for alpha-asynchronous updates.
+    pub fn updates(&self, generation: u64, idx: usize) -> bool {
+        match self {
+            Schedule::AlphaAsynchronous { alpha, seed } => random::unit(*seed, generation, idx, ALPHA_STREAM) < *alpha,
+            _ => true,
+        }
+    }
+}
+
+impl fmt::Display for Schedule {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Schedule::Synchronous => write!(f, "sync"),
+            Schedule::Sweep => write!(f, "sweep"),
+            Schedule::RandomSequential { seed } => write!(f, "random seed={}", seed),
+            Schedule::AlphaAsynchronous { alpha, seed } => write!(f, "alpha={} seed={}", alpha, seed),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Schedule;
+
+    #[test]
+    fn parses_schedules() {
+        assert_eq!(Schedule::parse("").unwrap(), Schedule::Synchronous);
+        assert_eq!(Schedule::parse("Sweep").unwrap(), Schedule::Sweep);
+        assert_eq!(Schedule::parse("seed=4 random").unwrap(), Schedule::RandomSequential { seed: 4 });
+        let alpha = Schedule::parse("alpha=0.25, seed=9").unwrap();
+        assert_eq!(alpha, Schedule::AlphaAsynchronous { alpha: 0.25, seed: 9 });
+        for schedule in [Schedule::Synchronous, Schedule::Sweep, Schedule::RandomSequential { seed: 2 }, alpha] {
+            assert_eq!(Schedule::parse(&schedule.to_string()).unwrap(), schedule);
+        }
+        assert!(Schedule::parse("alpha=0").is_err());
+        assert!(Schedule::parse("alpha").is_err());
+        assert!(Schedule::parse("sweep=1").is_err());
+        assert!(Schedule::parse("shuffle").is_err());
+    }
+}
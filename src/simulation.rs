@@ -0,0 +1,160 @@
+use std::any::Any;
+
+use crate::continuous::Continuous;
+use crate::elementary::Elementary;
+use crate::graph::Graph;
+use crate::hashlife::HashLife;
+use crate::life3d::Life3D;
+use crate::margolus::Margolus;
+use crate::sparse::Sparse;
+use crate::tableuniverse::TableUniverse;
+use crate::universe::Universe;
+
+/// The corners of a cell drawn as a polygon, as (row, column) positions.
+pub type Polygon = Vec<(f32, f32)>;
+
+/// A cellular automaton engine that the render loop can step and the renderer can draw.
+///
+/// Cells are addressed by row and column inside a `width` by `height` window,
+/// which is the whole universe for bounded engines.
+pub trait Simulation {
+    fn width(&self) -> usize;
+
+    fn height(&self) -> usize;
+
+    /// Gets how many generations have been computed since the universe was created or resized.
+    fn generation(&self) -> u64;
+
+    /// Compute the next generation.
+    fn tick(&mut self);
+
+    /// Compute the generation `generations` ahead of the current one.
+    ///
+    /// Engines that can skip ahead faster than one tick at a time override this.
+    fn step(&mut self, generations: u64) {
+        for _ in 0..generations {
+            self.tick();
+        }
+    }
+
+    /// Gets whether `step` skips ahead faster than one tick at a time.
+    fn can_jump(&self) -> bool {
+        false
+    }
+
+    /// Checks whether clicks can toggle cells in the current view.
+    fn editable(&self) -> Result<(), String> {
+        Ok(())
+    }
+
+    /// Go back to the previous generation, for engines running reversible rules.
+    fn step_back(&mut self) -> Result<(), String> {
+        Err(String::from("This engine cannot step backward"))
+    }
+
+    /// Gets the rule in the notation `set_rule` accepts.
+    fn rule(&self) -> String;
+
+    /// Parses and switches to a new rule, keeping the current cells.
+    fn set_rule(&mut self, rule: &str) -> Result<(), String>;
+
+    /// Gets the number of states a cell can be in under the current rule.
+    fn state_count(&self) -> u8;
+
+    /// Gets the color of every state, state 0 first.
+    fn palette(&self) -> Vec<[f32; 3]> {
+        default_palette(self.state_count())
+    }
+
+    /// Gets an array with row, column and state values for every cell to draw.
+    fn get_live_cells(&self) -> &[(f32,f32,f32)];
+
+    /// Toggle the value of a single cell between alive and dead.
+    fn toggle_cell(&mut self, row: usize, col: usize);
+
+    /// Toggle the value of many cells between alive and dead.
+    fn toggle_cells(&mut self, cells: &[(usize, usize)]);
+
+    /// Replace every cell with the given live cells.
+    fn set_cells(&mut self, cells: &[(usize, usize)]);
+
+    /// Set the width and height of the universe.
+    ///
+    /// Resets all cells to the dead state.
+    fn set_size(&mut self, width: Option<usize>, height: Option<usize>);
+
+    /// Gets how many tiles changed last generation, for engines that skip still areas.
+    fn active_tiles(&self) -> Option<usize> {
+        None
+    }
+
+    /// Gets whether odd rows sit half a cell to the right, as on a hexagonal grid.
+    fn hexagonal(&self) -> bool {
+        false
+    }
+
+    /// Gets how many live cells have each color under multi-color rules, or `None`
+    /// when live cells have a single color.
+    fn species_populations(&self) -> Option<Vec<usize>> {
+        None
+    }
+
+    /// Gets the corners of every cell as (row, column) positions and the state of
+    /// every cell, for engines whose cells are polygons instead of grid squares.
+    fn polygons(&self) -> Option<(&[Polygon], &[u8])> {
+        None
+    }
+
+    /// Gets the borders between cells running different rules, each a line from
+    /// (row, column) to (row, column) along the cell edges, for engines with
+    /// rule regions.
+    fn region_edges(&self) -> Option<&[[f32; 4]]> {
+        None
+    }
+
+    /// Gives access to the engine itself for settings only it has.
+    fn as_any_mut(&mut self) -> &mut dyn Any;
+}
+
+/// Creates an engine by the name used in the settings bar, e.g. "grid" or "hashlife".
+pub fn create(engine: &str, width: usize, height: usize) -> Result<Box<dyn Simulation>, String> {
+    match engine {
+        "grid" => Ok(Box::new(Universe::new(width, height))),
+        "hashlife" => Ok(Box::new(HashLife::new(width, height))),
+        "sparse" => Ok(Box::new(Sparse::new(width, height))),
+        "margolus" => Ok(Box::new(Margolus::new(width, height))),
+        "table" => Ok(Box::new(TableUniverse::new(width, height))),
+        "elementary" => Ok(Box::new(Elementary::new(width, height))),
+        "continuous" => Ok(Box::new(Continuous::new(width, height))),
+        "life3d" => Ok(Box::new(Life3D::new(width, height))),
+        "graph" => Ok(Box::new(Graph::new(width, height))),
+        _ => Err(format!("'{}' is not an engine", engine)),
+    }
+}
+
+/// Gets the colors of a rule with `states` states: live cells are light grey and
+/// dying cells fade from orange towards the background.
+pub fn default_palette(states: u8) -> Vec<[f32; 3]> {
+    let (young, old) = ([0.9, 0.5, 0.1], [0.4, 0.15, 0.3]);
+    (0..states.max(2)).map(|state| match state {
+        0 => [0.3, 0.3, 0.3],
+        1 => [0.8, 0.8, 0.8],
+        _ => {
+            let age = (state - 2) as f32 / (states.max(4) - 3) as f32;
+            [0, 1, 2].map(|c| young[c] + (old[c] - young[c]) * age)
+        }
+    }).collect()
+}
+
+/// Gets the colors of a cyclic rule with `states` states, spread evenly around
+/// the color wheel so every state follows on from the one before.
+pub fn cyclic_palette(states: u8) -> Vec<[f32; 3]> {
+    let (saturation, value) = (0.7, 0.9);
+    (0..states).map(|state| {
+        let hue = state as f32 / states as f32 * 6.0;
+        [5.0, 3.0, 1.0].map(|n: f32| {
+            let k = (n + hue) % 6.0;
+            value - value * saturation * k.min(4.0 - k).clamp(0.0, 1.0)
+        })
+    }).collect()
+}
@@ -0,0 +1,248 @@
+use std::any::Any;
+use std::collections::{HashMap, HashSet};
+
+use crate::rule::Rule;
+use crate::simulation::Simulation;
+
+const TILE_SIZE: i64 = 64;
+
+/// 64 rows of 64 cells, bit `c` of a row is the cell in column `c`.
+type Tile = [u64; TILE_SIZE as usize];
+
+/// An unbounded universe that only stores the 64x64 tiles holding live cells.
+///
+/// Tiles are allocated as a pattern grows into them and dropped once they are
+/// empty, so memory follows the population instead of the area. The `width` by
+/// `height` window starting at the origin is what gets drawn and edited.
+pub struct Sparse {
+    width: usize,
+    height: usize,
+    generation: u64,
+    rule: Rule,
+    tiles: HashMap<(i64, i64), Tile>,
+    live_cells: Vec<(f32,f32,f32)>
+}
+
+impl Sparse {
+
+    pub fn new(width: usize, height: usize) -> Sparse {
+        let mut sparse = Sparse {
+            width,
+            height,
+            generation: 0,
+            rule: Rule::default(),
+            tiles: HashMap::new(),
+            live_cells: Vec::new()
+        };
+        // Start with a glider when the window is big enough to hold it
+        if width >= 4 && height >= 4 {
+            let (row, col) = (height / 4, width / 4);
+            sparse.set_cells(&[(row, col), (row, col + 1), (row - 1, col), (row - 1, col - 1), (row + 1, col - 1)]);
+        }
+        sparse
+    }
+
+    /// Gets the tile holding a cell and the cell's row and column inside it.
+    fn locate(row: i64, col: i64) -> ((i64, i64), usize, usize) {
+        let tile = (row.div_euclid(TILE_SIZE), col.div_euclid(TILE_SIZE));
+        (tile, row.rem_euclid(TILE_SIZE) as usize, col.rem_euclid(TILE_SIZE) as usize)
+    }
+
+    fn get_cell(&self, row: i64, col: i64) -> bool {
+        let (tile, r, c) = Sparse::locate(row, col);
+        match self.tiles.get(&tile) {
+            Some(t) => t[r] >> c & 1 == 1,
+            None => false,
+        }
+    }
+
+    fn set_cell(&mut self, row: i64, col: i64, alive: bool) {
+        let (tile, r, c) = Sparse::locate(row, col);
+        let t = self.tiles.entry(tile).or_insert([0; TILE_SIZE as usize]);
+        if alive {
+            t[r] |= 1 << c;
+        }
+        else {
+            t[r] &= !(1 << c);
+        }
+        if t.iter().all(|row| *row == 0) {
+            self.tiles.remove(&tile);
+        }
+    }
+
+    /// Gets a row of a tile with one extra cell on each side taken from the
+    /// tiles to the west and east, bit `c + 1` is the cell in column `c`.
+    fn padded_row(&self, tile_row: i64, tile_col: i64, row: i64) -> u128 {
+        let (tile_row, row) = (tile_row + row.div_euclid(TILE_SIZE), row.rem_euclid(TILE_SIZE) as usize);
+        let word = |tile_col| self.tiles.get(&(tile_row, tile_col)).map_or(0, |t: &Tile| t[row]);
+        let west = word(tile_col - 1) >> (TILE_SIZE - 1);
+        let east = word(tile_col + 1) & 1;
+        (word(tile_col) as u128) << 1 | west as u128 | (east as u128) << (TILE_SIZE + 1)
+    }
+
+    /// Computes the next generation of a single tile.
+    fn next_tile(&self, tile_row: i64, tile_col: i64) -> Tile {
+        let mut next = [0; TILE_SIZE as usize];
+        let mut above = self.padded_row(tile_row, tile_col, -1);
+        let mut current = self.padded_row(tile_row, tile_col, 0);
+        for (r, next_row) in next.iter_mut().enumerate() {
+            let below = self.padded_row(tile_row, tile_col, r as i64 + 1);
+            for c in 0..TILE_SIZE as usize {
+                let count = (above >> c & 0b111).count_ones()
+                    + (current >> c & 0b101).count_ones()
+                    + (below >> c & 0b111).count_ones();
+                let state = (current >> (c + 1) & 1) as u8;
+                if self.rule.next_state(state, count as usize) == 1 {
+                    *next_row |= 1 << c;
+                }
+            }
+            above = current;
+            current = below;
+        }
+        next
+    }
+
+    fn refresh_live_cell_list(&mut self) {
+        self.live_cells.clear();
+        let (height, width) = (self.height as i64, self.width as i64);
+        for ((tile_row, tile_col), tile) in self.tiles.iter() {
+            for (r, bits) in tile.iter().enumerate() {
+                let row = tile_row * TILE_SIZE + r as i64;
+                if *bits == 0 || row < 0 || row >= height {
+                    continue;
+                }
+                for c in 0..TILE_SIZE {
+                    let col = tile_col * TILE_SIZE + c;
+                    if bits >> c & 1 == 1 && col >= 0 && col < width {
+                        self.live_cells.push((row as f32, col as f32, 1.0));
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl Simulation for Sparse {
+    fn width(&self) -> usize {
+        self.width
+    }
+
+    fn height(&self) -> usize {
+        self.height
+    }
+
+    fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    /// Only tiles with live cells and the tiles touching them are computed.
+    fn tick(&mut self) {
+        self.generation += 1;
+        let mut candidates = HashSet::new();
+        for (tile_row, tile_col) in self.tiles.keys() {
+            for dr in -1..=1 {
+                for dc in -1..=1 {
+                    candidates.insert((tile_row + dr, tile_col + dc));
+                }
+            }
+        }
+        let mut tiles = HashMap::with_capacity(self.tiles.len());
+        for (tile_row, tile_col) in candidates {
+            let tile = self.next_tile(tile_row, tile_col);
+            if tile.iter().any(|row| *row != 0) {
+                tiles.insert((tile_row, tile_col), tile);
+            }
+        }
+        self.tiles = tiles;
+        self.refresh_live_cell_list();
+    }
+
+    fn rule(&self) -> String {
+        self.rule.to_string()
+    }
+
+    fn set_rule(&mut self, rule: &str) -> Result<(), String> {
+        let rule = Rule::parse(rule)?;
+        if !rule.is_life_like() {
+            return Err(String::from("The sparse engine only runs two state rules on the eight cell neighborhood"));
+        }
+        if rule.next_state(0, 0) == 1 {
+            return Err(String::from("The sparse engine cannot run rules where cells are born with no neighbors"));
+        }
+        self.rule = rule;
+        Ok(())
+    }
+
+    fn state_count(&self) -> u8 {
+        2
+    }
+
+    fn get_live_cells(&self) -> &[(f32,f32,f32)] {
+        &self.live_cells
+    }
+
+    fn toggle_cell(&mut self, row: usize, col: usize) {
+        self.toggle_cells(&[(row, col)]);
+    }
+
+    fn toggle_cells(&mut self, cells: &[(usize, usize)]) {
+        for (row, col) in cells {
+            let (row, col) = (*row as i64, *col as i64);
+            let alive = self.get_cell(row, col);
+            self.set_cell(row, col, !alive);
+        }
+        self.refresh_live_cell_list();
+    }
+
+    fn set_cells(&mut self, cells: &[(usize, usize)]) {
+        self.tiles.clear();
+        for (row, col) in cells {
+            self.set_cell(*row as i64, *col as i64, true);
+        }
+        self.refresh_live_cell_list();
+    }
+
+    fn set_size(&mut self, width: Option<usize>, height: Option<usize>) {
+        if let Some(w) = width {
+            self.width = w;
+        }
+        if let Some(h) = height {
+            self.height = h;
+        }
+        if width.is_some() || height.is_some() {
+            self.generation = 0;
+            self.set_cells(&[]);
+        }
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Sparse;
+    use crate::simulation::Simulation;
+
+    #[test]
+    fn glider_flies_across_tiles() {
+        let mut sparse = Sparse::new(100, 100);
+        // The glider moves up and to the right one cell every 4 generations
+        for _ in 0..4 * 200 {
+            sparse.tick();
+        }
+        assert_eq!(sparse.tiles.values().map(|t| t.iter().map(|r| r.count_ones()).sum::<u32>()).sum::<u32>(), 5);
+        // Only the tiles around the glider are kept, never the ones it left behind
+        assert!(sparse.tiles.len() <= 4);
+        assert!(sparse.tiles.keys().all(|(row, col)| *row < -1 && *col > 1));
+    }
+
+    #[test]
+    fn starts_empty_when_too_small_for_a_glider() {
+        for (width, height) in [(1, 1), (3, 10), (10, 3)] {
+            assert!(Sparse::new(width, height).get_live_cells().is_empty());
+        }
+        assert_eq!(Sparse::new(4, 4).get_live_cells().len(), 5);
+    }
+}
@@ -0,0 +1,182 @@
+use std::any::Any;
+
+use crate::ruletable::{self, RuleTable};
+use crate::simulation::Simulation;
+
+/// A wrapping universe of multi-state cells run by a rule table, Wireworld to start with.
+pub struct TableUniverse {
+    width: usize,
+    height: usize,
+    generation: u64,
+    table: RuleTable,
+    cells: Vec<u8>,
+    old_cells: Vec<u8>,
+    live_cells: Vec<(f32,f32,f32)>
+}
+
+impl TableUniverse {
+
+    /// Creates an empty universe running Wireworld.
+    pub fn new(width: usize, height: usize) -> TableUniverse {
+        TableUniverse {
+            width,
+            height,
+            generation: 0,
+            table: RuleTable::parse(ruletable::WIREWORLD).unwrap(),
+            cells: vec![0; width * height],
+            old_cells: vec![0; width * height],
+            live_cells: Vec::new()
+        }
+    }
+
+    fn get_index(&self, row: usize, col: usize) -> usize {
+        row * self.width + col
+    }
+
+    fn refresh_live_cell_list(&mut self) {
+        self.live_cells.clear();
+        for (idx, state) in self.cells.iter().enumerate() {
+            if *state != 0 {
+                let (row, col) = (idx / self.width, idx % self.width);
+                self.live_cells.push((row as f32, col as f32, *state as f32));
+            }
+        }
+    }
+}
+
+impl Simulation for TableUniverse {
+    fn width(&self) -> usize {
+        self.width
+    }
+
+    fn height(&self) -> usize {
+        self.height
+    }
+
+    fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    fn tick(&mut self) {
+        self.generation += 1;
+        std::mem::swap(&mut self.cells, &mut self.old_cells);
+        let (width, height) = (self.width as isize, self.height as isize);
+        let mut states = vec![0; self.table.neighbors().len() + 1];
+        for row in 0..height {
+            for col in 0..width {
+                states[0] = self.old_cells[(row * width + col) as usize];
+                for (state, (dr, dc)) in states[1..].iter_mut().zip(self.table.neighbors()) {
+                    let (r, c) = ((row + dr).rem_euclid(height), (col + dc).rem_euclid(width));
+                    *state = self.old_cells[(r * width + c) as usize];
+                }
+                self.cells[(row * width + col) as usize] = self.table.next_state(&states);
+            }
+        }
+        self.refresh_live_cell_list();
+    }
+
+    fn rule(&self) -> String {
+        self.table.name().to_string()
+    }
+
+    /// Takes the text of a `.rule` file, the name of a built in table such as
+    /// "wireworld", or the name of the current table to keep it.
+    /// Cells in states the new table does not have are cleared.
+    fn set_rule(&mut self, rule: &str) -> Result<(), String> {
+        let rule = rule.trim();
+        if rule == self.table.name() {
+            return Ok(());
+        }
+        self.table = if rule.contains('@') {
+            RuleTable::parse(rule)?
+        } else if rule.eq_ignore_ascii_case("wireworld") {
+            RuleTable::parse(ruletable::WIREWORLD)?
+        } else {
+            return Err(format!("'{}' is not a built in rule table, load a .rule file instead", rule));
+        };
+        let states = self.table.states();
+        self.cells.iter_mut().filter(|state| **state >= states).for_each(|state| *state = 0);
+        self.refresh_live_cell_list();
+        Ok(())
+    }
+
+    fn state_count(&self) -> u8 {
+        self.table.states()
+    }
+
+    fn palette(&self) -> Vec<[f32; 3]> {
+        self.table.colors().to_vec()
+    }
+
+    fn get_live_cells(&self) -> &[(f32,f32,f32)] {
+        &self.live_cells
+    }
+
+    fn toggle_cell(&mut self, row: usize, col: usize) {
+        self.toggle_cells(&[(row, col)]);
+    }
+
+    /// Cells step through every state, so clicking draws e.g. Wireworld conductors.
+    fn toggle_cells(&mut self, cells: &[(usize, usize)]) {
+        let states = self.table.states();
+        for (row, col) in cells {
+            let idx = self.get_index(*row, *col);
+            self.cells[idx] = (self.cells[idx] + 1) % states;
+        }
+        self.refresh_live_cell_list();
+    }
+
+    /// The given cells are set to state 1.
+    fn set_cells(&mut self, cells: &[(usize, usize)]) {
+        self.cells.iter_mut().for_each(|state| *state = 0);
+        for (row, col) in cells {
+            let idx = self.get_index(*row, *col);
+            self.cells[idx] = 1;
+        }
+        self.refresh_live_cell_list();
+    }
+
+    fn set_size(&mut self, width: Option<usize>, height: Option<usize>) {
+        if let Some(w) = width {
+            self.width = w;
+        }
+        if let Some(h) = height {
+            self.height = h;
+        }
+        if width.is_some() || height.is_some() {
+            self.generation = 0;
+            self.cells = vec![0; self.width * self.height];
+            self.old_cells = vec![0; self.width * self.height];
+            self.refresh_live_cell_list();
+        }
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::TableUniverse;
+    use crate::simulation::Simulation;
+
+    #[test]
+    fn wireworld_electron_runs_along_a_wire() {
+        let mut universe = TableUniverse::new(20, 5);
+        let wire: Vec<(usize, usize)> = (2..18).map(|col| (2, col)).collect();
+        universe.toggle_cells(&wire);
+        universe.toggle_cells(&wire);
+        universe.toggle_cells(&wire);
+        // A tail and a head at the start of the wire
+        universe.toggle_cells(&[(2, 2), (2, 2), (2, 2), (2, 3), (2, 3)]);
+        assert_eq!(universe.cells[2 * 20 + 2], 2);
+        assert_eq!(universe.cells[2 * 20 + 3], 1);
+        for step in 1..=10 {
+            universe.tick();
+            let head = universe.cells.iter().position(|state| *state == 1).unwrap();
+            assert_eq!(head, 2 * 20 + 3 + step);
+            assert_eq!(universe.cells[head - 1], 2);
+        }
+    }
+}
@@ -0,0 +1,107 @@
+use std::fmt;
+
+/// How the edges of the universe are joined together.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Topology {
+    /// No edges are joined, everything past the border is dead.
+    Plane,
+    /// Left joins right and top joins bottom.
+    #[default]
+    Torus,
+    /// Left joins right, top and bottom are dead borders.
+    Cylinder,
+    /// Left joins right, top joins bottom mirrored left to right.
+    KleinBottle,
+    /// Top joins bottom mirrored left to right and left joins right mirrored top to bottom.
+    CrossSurface,
+}
+
+impl Topology {
+
+    /// Parses the name of a topology as used by the settings bar, e.g. "torus".
+    pub fn parse(name: &str) -> Result<Topology, String> {
+        match name.trim().to_ascii_lowercase().as_str() {
+            "plane" => Ok(Topology::Plane),
+            "torus" => Ok(Topology::Torus),
+            "cylinder" => Ok(Topology::Cylinder),
+            "klein" => Ok(Topology::KleinBottle),
+            "cross" => Ok(Topology::CrossSurface),
+            _ => Err(format!("'{}' is not a topology", name)),
+        }
+    }
+
+    /// Maps a position that may be past the edges of a universe to the cell it
+    /// lands on, or `None` if it falls off a dead border.
+    pub fn resolve(&self, row: isize, col: isize, width: usize, height: usize) -> Option<(usize, usize)> {
+        let (w, h) = (width as isize, height as isize);
+        if row >= 0 && row < h && col >= 0 && col < w {
+            return Some((row as usize, col as usize));
+        }
+        let (wrap_rows, twist_rows, wrap_cols, twist_cols) = match self {
+            Topology::Plane => (false, false, false, false),
+            Topology::Torus => (true, false, true, false),
+            Topology::Cylinder => (false, false, true, false),
+            Topology::KleinBottle => (true, true, true, false),
+            Topology::CrossSurface => (true, true, true, true),
+        };
+        let (mut row, mut col) = (row, col);
+        if row < 0 || row >= h {
+            if !wrap_rows {
+                return None;
+            }
+            // Every odd crossing of a twisted edge mirrors the other axis
+            if twist_rows && row.div_euclid(h) % 2 != 0 {
+                col = w - 1 - col;
+            }
+            row = row.rem_euclid(h);
+        }
+        if col < 0 || col >= w {
+            if !wrap_cols {
+                return None;
+            }
+            if twist_cols && col.div_euclid(w) % 2 != 0 {
+                row = h - 1 - row;
+            }
+            col = col.rem_euclid(w);
+        }
+        Some((row as usize, col as usize))
+    }
+}
+
+impl fmt::Display for Topology {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Topology::Plane => "plane",
+            Topology::Torus => "torus",
+            Topology::Cylinder => "cylinder",
+            Topology::KleinBottle => "klein",
+            Topology::CrossSurface => "cross",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Topology;
+
+    #[test]
+    fn resolves_edges() {
+        assert_eq!(Topology::Torus.resolve(-1, -1, 10, 5), Some((4, 9)));
+        assert_eq!(Topology::Plane.resolve(-1, 3, 10, 5), None);
+        assert_eq!(Topology::Cylinder.resolve(2, 10, 10, 5), Some((2, 0)));
+        assert_eq!(Topology::Cylinder.resolve(5, 2, 10, 5), None);
+        assert_eq!(Topology::KleinBottle.resolve(5, 2, 10, 5), Some((0, 7)));
+        assert_eq!(Topology::KleinBottle.resolve(2, -1, 10, 5), Some((2, 9)));
+        assert_eq!(Topology::CrossSurface.resolve(1, 10, 10, 5), Some((3, 0)));
+        assert_eq!(Topology::CrossSurface.resolve(-1, 0, 10, 5), Some((4, 9)));
+    }
+
+    #[test]
+    fn parses_names() {
+        for name in ["plane", "torus", "cylinder", "klein", "cross"] {
+            assert_eq!(Topology::parse(name).unwrap().to_string(), name);
+        }
+        assert!(Topology::parse("sphere").is_err());
+    }
+}
@@ -0,0 +1,264 @@
+use std::fmt;
+
+const MAX_COLORS: usize = 255;
+const MAX_ANTS: usize = 100;
+
+/// A turn an ant makes after leaving a cell, relative to where it was heading.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Turn {
+    None,
+    Right,
+    /// A u-turn.
+    Back,
+    Left,
+}
+
+impl Turn {
+
+    /// Reads a turn from Golly's turmite codes: 1 for none, 2 right, 4 u-turn and 8 left.
+    fn from_code(code: u8) -> Option<Turn> {
+        match code {
+            1 => Some(Turn::None),
+            2 => Some(Turn::Right),
+            4 => Some(Turn::Back),
+            8 => Some(Turn::Left),
+            _ => None,
+        }
+    }
+
+    fn code(&self) -> u8 {
+        match self {
+            Turn::None => 1,
+            Turn::Right => 2,
+            Turn::Back => 4,
+            Turn::Left => 8,
+        }
+    }
+
+    fn from_letter(letter: char) -> Option<Turn> {
+        match letter.to_ascii_uppercase() {
+            'N' => Some(Turn::None),
+            'R' => Some(Turn::Right),
+            'U' => Some(Turn::Back),
+            'L' => Some(Turn::Left),
+            _ => None,
+        }
+    }
+
+    fn letter(&self) -> char {
+        match self {
+            Turn::None => 'N',
+            Turn::Right => 'R',
+            Turn::Back => 'U',
+            Turn::Left => 'L',
+        }
+    }
+
+    /// Gets how many quarter turns clockwise the turn is.
+    fn quarters(&self) -> u8 {
+        match self {
+            Turn::None => 0,
+            Turn::Right => 1,
+            Turn::Back => 2,
+            Turn::Left => 3,
+        }
+    }
+}
+
+/// What an ant in some state does on a cell of some color.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Transition {
+    write: u8,
+    turn: Turn,
+    next: u8,
+}
+
+/// An ant walking the grid, heading 0 to 3 for north, east, south and west.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Ant {
+    pub row: usize,
+    pub col: usize,
+    pub heading: u8,
+    state: u8,
+}
+
+/// A turmite, ants that read the color of the cell under them, write a new
+/// color, turn and step forward every tick, written like "RL ants=2".
+///
+/// Langton's ant and its generalizations are a string of turns such as "RL" or
+/// "LLRR": on a cell of color n an ant turns by letter n (L, R, N for none or U
+/// for a u-turn) and leaves the cell in color n + 1, wrapping to 0. Turmites with
+/// states of their own use Golly's table such as "{{{1,2,0},{0,8,0}}}", which
+/// has a {color, turn, state} entry per color for each state of the ant, with
+/// the turns 1 for none, 2 right, 4 u-turn and 8 left.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Turmite {
+    // Transitions for every state of the ant, one for every color
+    table: Vec<Vec<Transition>>,
+    ants: usize,
+}
+
+impl Turmite {
+
+    /// Parses a string of turns or a Golly table, followed by an optional "ants=n".
+    pub fn parse(text: &str) -> Result<Turmite, String> {
+        let text = text.trim();
+        let (program, settings) = match text.rfind('}') {
+            Some(end) => text.split_at(end + 1),
+            None => text.split_once([' ', ',']).unwrap_or((text, "")),
+        };
+        let mut ants = 1;
+        for setting in settings.split([' ', ',']).filter(|s| !s.is_empty()) {
+            match setting.split_once('=') {
+                Some((key, value)) if key.eq_ignore_ascii_case("ants") => match value.parse::<usize>() {
+                    Ok(n) if (1..=MAX_ANTS).contains(&n) => ants = n,
+                    _ => return Err(format!("'{}' is not a number of ants between 1 and {}", setting, MAX_ANTS)),
+                },
+                _ => return Err(format!("'{}' is not an ants=n setting", setting)),
+            }
+        }
+        let table = if program.starts_with('{') {
+            Turmite::parse_table(program)?
+        } else {
+            Turmite::parse_turns(program)?
+        };
+        Ok(Turmite { table, ants })
+    }
+
+    /// Gets how many colors of cells the turmite reads and writes.
+    pub fn colors(&self) -> usize {
+        self.table[0].len()
+    }
+
+    /// Gets the ants at their starting positions, spread out along the middle
+    /// row of the universe and heading north.
+    pub fn place_ants(&self, width: usize, height: usize) -> Vec<Ant> {
+        (0..self.ants).map(|i| Ant {
+            row: height / 2,
+            col: width * (i + 1) / (self.ants + 1),
+            heading: 0,
+            state: 0,
+        }).collect()
+    }
+
+    /// Turns an ant standing on a cell of `color` and moves it to its next state,
+    /// giving the color it leaves the cell in. Colors the turmite has no entry for
+    /// count as color 0.
+    pub fn step(&self, ant: &mut Ant, color: u8) -> u8 {
+        let transitions = &self.table[ant.state as usize];
+        let transition = transitions.get(color as usize).unwrap_or(&transitions[0]);
+        ant.heading = (ant.heading + transition.turn.quarters()) % 4;
+        ant.state = transition.next;
+        transition.write
+    }
+
+    fn parse_turns(turns: &str) -> Result<Vec<Vec<Transition>>, String> {
+        if !(2..=MAX_COLORS).contains(&turns.chars().count()) {
+            return Err(format!("'{}' should have between 2 and {} turns such as RL", turns, MAX_COLORS));
+        }
+        let colors = turns.chars().count();
+        let transitions = turns.chars().enumerate().map(|(color, letter)| {
+            let turn = Turn::from_letter(letter).ok_or_else(|| format!("'{}' is not a turn, use L, R, N or U", letter))?;
+            Ok(Transition { write: ((color + 1) % colors) as u8, turn, next: 0 })
+        }).collect::<Result<Vec<Transition>, String>>()?;
+        Ok(vec![transitions])
+    }
+
+    /// Parses a Golly turmite table, every number inside the third level of
+    /// braces being part of a {color, turn, state} entry.
+    fn parse_table(text: &str) -> Result<Vec<Vec<Transition>>, String> {
+        let mut table: Vec<Vec<Transition>> = Vec::new();
+        let mut depth = 0;
+        let mut entry = String::new();
+        for c in text.chars().filter(|c| !c.is_whitespace()) {
+            match c {
+                '{' => {
+                    depth += 1;
+                    if depth == 2 {
+                        table.push(Vec::new());
+                    }
+                }
+                '}' if depth == 3 => {
+                    let numbers = entry.split(',').map(|n| n.parse::<u8>()).collect::<Result<Vec<u8>, _>>();
+                    let transition = match numbers.as_deref() {
+                        Ok([write, turn, next]) => Turn::from_code(*turn).map(|turn| Transition { write: *write, turn, next: *next }),
+                        _ => None,
+                    };
+                    let transition = transition.ok_or_else(|| format!("'{{{}}}' is not a {{color, turn, state}} entry", entry))?;
+                    table.last_mut().unwrap().push(transition);
+                    entry.clear();
+                    depth -= 1;
+                }
+                '}' if depth > 0 => depth -= 1,
+                _ if depth == 3 => entry.push(c),
+                ',' => {}
+                _ => return Err(format!("'{}' is not part of a turmite table", c)),
+            }
+        }
+        if depth != 0 || table.is_empty() {
+            return Err(format!("'{}' should be a table such as {{{{{{1,2,0}},{{0,8,0}}}}}}", text));
+        }
+        let colors = table[0].len();
+        if colors < 2 || table.iter().any(|transitions| transitions.len() != colors) {
+            return Err(String::from("every state of a turmite needs an entry for each of at least 2 colors"));
+        }
+        let bad = table.iter().flatten().find(|t| t.write as usize >= colors || t.next as usize >= table.len());
+        if let Some(t) = bad {
+            return Err(format!("entry {{{},{},{}}} writes a color or goes to a state the table does not have", t.write, t.turn.code(), t.next));
+        }
+        Ok(table)
+    }
+
+    /// Gets whether the turmite is a string of turns: a single state, stepping
+    /// every cell on to the next color.
+    fn is_turns(&self) -> bool {
+        self.table.len() == 1
+            && self.table[0].iter().enumerate().all(|(color, t)| t.write as usize == (color + 1) % self.colors())
+    }
+}
+
+impl fmt::Display for Turmite {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.is_turns() {
+            for transition in self.table[0].iter() {
+                write!(f, "{}", transition.turn.letter())?;
+            }
+        } else {
+            write!(f, "{{")?;
+            for (state, transitions) in self.table.iter().enumerate() {
+                let entries: Vec<String> = transitions.iter()
+                    .map(|t| format!("{{{},{},{}}}", t.write, t.turn.code(), t.next))
+                    .collect();
+                write!(f, "{}{{{}}}", if state > 0 { "," } else { "" }, entries.join(","))?;
+            }
+            write!(f, "}}")?;
+        }
+        if self.ants > 1 {
+            write!(f, " ants={}", self.ants)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Turmite;
+
+    #[test]
+    fn parses_turmites() {
+        assert_eq!(Turmite::parse("rl").unwrap().to_string(), "RL");
+        assert_eq!(Turmite::parse("LLRR ants=3").unwrap().to_string(), "LLRR ants=3");
+        // Langton's ant as a table is the same turmite as its string of turns
+        assert_eq!(Turmite::parse("{{{1,2,0},{0,8,0}}}").unwrap(), Turmite::parse("RL").unwrap());
+        let spiral = Turmite::parse("{{{1,8,1},{1,8,1}},{{1,2,1},{0,1,0}}} ants=2").unwrap();
+        assert_eq!(spiral.to_string(), "{{{1,8,1},{1,8,1}},{{1,2,1},{0,1,0}}} ants=2");
+        assert_eq!(spiral.colors(), 2);
+        assert!(Turmite::parse("R").is_err());
+        assert!(Turmite::parse("RX").is_err());
+        assert!(Turmite::parse("RL ants=0").is_err());
+        assert!(Turmite::parse("{{{1,3,0},{0,8,0}}}").is_err());
+        assert!(Turmite::parse("{{{2,2,0},{0,8,0}}}").is_err());
+        assert!(Turmite::parse("{{{1,2,1},{0,8,0}}}").is_err());
+        assert!(Turmite::parse("{{{1,2,0},{0,8,0}}").is_err());
+    }
+}
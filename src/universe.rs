@@ -1,15 +1,89 @@
 extern crate fixedbitset;
 
+use std::any::Any;
+use std::collections::BTreeMap;
+use std::ops::Range;
+
+use crate::bitwise;
+use crate::kernel::Kernel;
+use crate::noise::Noise;
+use crate::obstacle::Obstacle;
+use crate::parallel;
+use crate::random;
+use crate::rule::{Neighborhood, Rule};
+use crate::schedule::Schedule;
+use crate::simulation::{self, Simulation};
+use crate::topology::Topology;
+use crate::turmite::{Ant, Turmite};
 use crate::utils;
 
 use fixedbitset::FixedBitSet;
 
+const MOORE_OFFSETS: [(isize, isize); 8] = [
+    (-1, -1), (-1, 0), (-1, 1),
+    (0, -1),           (0, 1),
+    (1, -1),  (1, 0),  (1, 1),
+];
+
+// Red, blue, green and yellow live cells of multi-color rules
+const SPECIES_COLORS: [[f32; 3]; 4] = [
+    [0.9, 0.3, 0.3],
+    [0.35, 0.55, 0.95],
+    [0.35, 0.85, 0.4],
+    [0.95, 0.85, 0.3],
+];
+
+// Row and column steps of ants heading north, east, south and west
+const HEADINGS: [(isize, isize); 4] = [(-1, 0), (0, 1), (1, 0), (0, -1)];
+
+const ANT_COLOR: [f32; 3] = [0.95, 0.2, 0.2];
+
+// Walls, always alive cells and emitters, drawn over the cells after the ants
+const OBSTACLE_COLORS: [[f32; 3]; 3] = [
+    [0.45, 0.45, 0.6],
+    [0.95, 0.95, 0.95],
+    [0.2, 0.85, 0.9],
+];
+
+// Most rules the regions of a universe can run besides its own rule
+const MAX_REGION_RULES: usize = 255;
+
+// Random numbers filling the universe when it switches to a cyclic rule
+const CYCLIC_FILL_STREAM: u64 = 5;
+
+// Rows and columns of cells in an active region tile, at least the largest rule range
+const TILE_SIZE: usize = 32;
+
 pub struct Universe {
     width: usize,
     height: usize,
     cells: FixedBitSet,
     old_cells: FixedBitSet,
-    live_cells: Vec<(f32,f32)>
+    dying: Vec<u8>,
+    old_dying: Vec<u8>,
+    // Colors of live cells under multi-color rules, empty otherwise
+    colors: Vec<u8>,
+    old_colors: Vec<u8>,
+    live_cells: Vec<(f32,f32,f32)>,
+    // Live and dying cells of each tile, listed again only when the tile changes
+    tile_live_cells: Vec<Vec<(f32,f32,f32)>>,
+    generation: u64,
+    rule: Rule,
+    topology: Topology,
+    noise: Noise,
+    schedule: Schedule,
+    turmite: Option<Turmite>,
+    ants: Vec<Ant>,
+    // Cells the rule leaves alone, by index
+    obstacles: BTreeMap<usize, Obstacle>,
+    // Rules painted over parts of the universe, and for every cell 0 for the
+    // universe's rule or 1 plus its region rule, empty without regions
+    region_rules: Vec<Rule>,
+    rule_regions: Vec<u8>,
+    // Borders between cells running different rules, as (row, column, row, column)
+    region_edges: Vec<[f32; 4]>,
+    // Tiles with a cell that changed last generation, row major over the tile grid
+    active_tiles: FixedBitSet
 }
 
 impl Universe {
@@ -21,155 +95,774 @@ impl Universe {
             height,
             cells: FixedBitSet::with_capacity(size),
             old_cells: FixedBitSet::with_capacity(size),
-            live_cells: Vec::new()
+            dying: Vec::new(),
+            old_dying: Vec::new(),
+            colors: Vec::new(),
+            old_colors: Vec::new(),
+            live_cells: Vec::new(),
+            tile_live_cells: Vec::new(),
+            generation: 0,
+            rule: Rule::default(),
+            topology: Topology::default(),
+            noise: Noise::default(),
+            schedule: Schedule::default(),
+            turmite: None,
+            ants: Vec::new(),
+            obstacles: BTreeMap::new(),
+            region_rules: Vec::new(),
+            rule_regions: Vec::new(),
+            region_edges: Vec::new(),
+            active_tiles: FixedBitSet::new()
         };
-        universe.add_glider((width * width / 4) + (height / 4));
+        universe.activate_all_tiles();
+        // Start with a glider when the universe is big enough to hold it
+        if width >= 4 && height >= 4 {
+            universe.add_glider(universe.get_index(height / 4, width / 4));
+        }
+        universe.refresh_live_cell_list();
         universe
     }
 
-    pub fn width(&self) -> usize {
-        self.width
+    /// Set how cells at the edges of the universe find their neighbors.
+    pub fn set_topology(&mut self, topology: Topology) {
+        self.topology = topology;
+        self.activate_all_tiles();
     }
 
-    pub fn height(&self) -> usize {
-        self.height
+    /// Set the random births, survivals and flips layered over the rule.
+    pub fn set_noise(&mut self, noise: Noise) {
+        self.noise = noise;
+        self.activate_all_tiles();
     }
 
-    pub fn tick(&mut self) {
-        // let _timer = utils::Timer::new("Universe::tick");
-        self.live_cells.clear();
-        self.old_cells.clone_from(&self.cells);
+    pub fn noise(&self) -> &Noise {
+        &self.noise
+    }
+
+    /// Set the order cells are updated in within a generation.
+    pub fn set_schedule(&mut self, schedule: Schedule) {
+        self.schedule = schedule;
+        self.activate_all_tiles();
+    }
+
+    pub fn schedule(&self) -> Schedule {
+        self.schedule
+    }
+
+    /// Set the ants walking the grid, placing them in the middle of the universe,
+    /// or take them away with `None`. The rule needs a state for every color the
+    /// turmite writes.
+    pub fn set_turmite(&mut self, turmite: Option<Turmite>) -> Result<(), String> {
+        if let Some(ref turmite) = turmite {
+            if turmite.colors() > self.state_count() as usize {
+                return Err(format!("the turmite has {} colors and the rule only {} states", turmite.colors(), self.state_count()));
+            }
+        }
+        self.ants = turmite.as_ref().map_or_else(Vec::new, |turmite| turmite.place_ants(self.width, self.height));
+        self.turmite = turmite;
+        self.refresh_live_cell_list();
+        Ok(())
+    }
+
+    pub fn turmite(&self) -> Option<&Turmite> {
+        self.turmite.as_ref()
+    }
+
+    /// Paints cells with a rule they run instead of the universe's rule, or the
+    /// universe's rule again when `rule` is the same.
+    pub fn paint_rule(&mut self, cells: &[(usize, usize)], rule: &str) -> Result<(), String> {
+        let rule = Rule::parse(rule)?;
+        Universe::check_region_rule(&self.rule, &rule)?;
+        let region = if rule == self.rule {
+            0
+        } else if let Some(i) = self.region_rules.iter().position(|r| *r == rule) {
+            i + 1
+        } else if self.region_rules.len() < MAX_REGION_RULES {
+            self.region_rules.push(rule);
+            self.region_rules.len()
+        } else {
+            return Err(format!("a universe can only have {} region rules", MAX_REGION_RULES));
+        };
+        if self.rule_regions.is_empty() {
+            self.rule_regions = vec![0; self.width * self.height];
+        }
+        for (row, col) in cells {
+            let idx = self.get_index(*row, *col);
+            self.rule_regions[idx] = region as u8;
+        }
+        self.activate_all_tiles();
+        self.refresh_region_edges();
+        Ok(())
+    }
+
+    /// Takes the painted regions away, every cell running the universe's rule again.
+    pub fn clear_regions(&mut self) {
+        self.region_rules.clear();
+        self.rule_regions.clear();
+        self.region_edges.clear();
+        self.activate_all_tiles();
+    }
+
+    /// Gets the rule a cell runs.
+    fn rule_at(&self, idx: usize) -> &Rule {
+        match self.rule_regions.get(idx) {
+            Some(region) if *region > 0 => &self.region_rules[*region as usize - 1],
+            _ => &self.rule,
+        }
+    }
+
+    /// Checks that a rule can run in a region of a universe running `rule`: both
+    /// only look at the cells right around a cell, on the same kind of grid, and
+    /// have the same states and colors.
+    fn check_region_rule(rule: &Rule, region_rule: &Rule) -> Result<(), String> {
+        let hexagonal = |rule: &Rule| rule.neighborhood() == Neighborhood::Hexagonal;
+        let range_one = |rule: &Rule| {
+            !rule.is_larger_than_life() && rule.cyclic_threshold().is_none() && rule.kernel().is_none()
+        };
+        if !range_one(rule) || !range_one(region_rule) {
+            return Err(String::from("regions only run rules on the cells right around a cell"));
+        }
+        if rule.states() != region_rule.states() || rule.species() != region_rule.species() || hexagonal(rule) != hexagonal(region_rule) {
+            return Err(format!("'{}' and '{}' need the same states, colors and grid to share a universe", rule, region_rule));
+        }
+        Ok(())
+    }
+
+    /// Finds the borders between neighboring cells running different rules.
+    fn refresh_region_edges(&mut self) {
+        self.region_edges.clear();
         for row in 0..self.height {
             for col in 0..self.width {
-                let idx = self.get_index(row, col);
-                let cell = self.old_cells[idx];
-                let live_neighbors = self.live_neighbor_count(row, col);
-                let new_cell = match (cell, live_neighbors) {
-                    (false, 3) => true,
-                    (false, _) => false,
-                    (true, 2) => true,
-                    (true, 3) => true,
-                    (true, _) => false
-                };
-                if new_cell {
-                    self.live_cells.push((row as f32, col as f32));
+                let region = self.rule_regions[self.get_index(row, col)];
+                let (top, left) = (row as f32, col as f32);
+                if col + 1 < self.width && self.rule_regions[self.get_index(row, col + 1)] != region {
+                    self.region_edges.push([top, left + 1.0, top + 1.0, left + 1.0]);
+                }
+                if row + 1 < self.height && self.rule_regions[self.get_index(row + 1, col)] != region {
+                    self.region_edges.push([top + 1.0, left, top + 1.0, left + 1.0]);
                 }
-                self.cells.set(idx, new_cell);
             }
         }
     }
 
-    /// Gets an array with row and column values for every live cell in the universe.
-    pub fn get_live_cells(&self) -> &[(f32,f32)] {
-        &self.live_cells
+    /// Makes a cell an obstacle, or a normal cell again when it already is that
+    /// obstacle. The cell takes the obstacle's state right away.
+    pub fn toggle_obstacle(&mut self, row: usize, col: usize, obstacle: Obstacle) {
+        let idx = self.get_index(row, col);
+        if self.obstacles.get(&idx) == Some(&obstacle) {
+            self.obstacles.remove(&idx);
+        } else {
+            self.obstacles.insert(idx, obstacle);
+            self.set_state(idx, obstacle.state(self.generation));
+        }
+        self.active_tiles.insert(self.tile_of(idx));
+        self.refresh_live_cell_list();
+    }
+
+    fn tile_columns(&self) -> usize {
+        self.width.div_ceil(TILE_SIZE)
+    }
+
+    fn tile_rows(&self) -> usize {
+        self.height.div_ceil(TILE_SIZE)
+    }
+
+    fn tile_of(&self, idx: usize) -> usize {
+        let (row, col) = (idx / self.width, idx % self.width);
+        row / TILE_SIZE * self.tile_columns() + col / TILE_SIZE
+    }
+
+    /// Gets the rows and columns of cells in a tile, `columns` being the tiles in a row.
+    fn tile_span(&self, tile: usize, columns: usize) -> (Range<usize>, Range<usize>) {
+        let (top, left) = (tile / columns * TILE_SIZE, tile % columns * TILE_SIZE);
+        (top..(top + TILE_SIZE).min(self.height), left..(left + TILE_SIZE).min(self.width))
+    }
+
+    /// Marks every tile as changed, so the next generation is computed in full.
+    fn activate_all_tiles(&mut self) {
+        self.active_tiles = FixedBitSet::with_capacity(self.tile_columns() * self.tile_rows());
+        self.active_tiles.insert_range(..);
+    }
+
+    /// Gets the tiles whose cells can change this generation: the tiles that changed
+    /// last generation and the tiles around them.
+    ///
+    /// A cell whose neighborhood stayed the same keeps its state, so every other
+    /// tile can be copied over unchanged.
+    fn dirty_tiles(&self) -> FixedBitSet {
+        let (columns, rows) = (self.tile_columns(), self.tile_rows());
+        let mut dirty = FixedBitSet::with_capacity(columns * rows);
+        let mut touches_edge = false;
+        for tile in self.active_tiles.ones() {
+            let (tile_row, tile_col) = (tile / columns, tile % columns);
+            touches_edge |= tile_row == 0 || tile_col == 0 || tile_row == rows - 1 || tile_col == columns - 1;
+            for r in tile_row.saturating_sub(1)..=(tile_row + 1).min(rows - 1) {
+                for c in tile_col.saturating_sub(1)..=(tile_col + 1).min(columns - 1) {
+                    dirty.insert(r * columns + c);
+                }
+            }
+        }
+        // Which tiles meet across the edges depends on the topology, so a change at
+        // any edge dirties all of them
+        if touches_edge {
+            for r in 0..rows {
+                dirty.insert(r * columns);
+                dirty.insert(r * columns + columns - 1);
+            }
+            for c in 0..columns {
+                dirty.insert(c);
+                dirty.insert((rows - 1) * columns + c);
+            }
+        }
+        dirty
+    }
+
+    /// Gets the tiles among `candidates` where the new generation differs from the
+    /// old one, every other tile is left out without being compared.
+    fn changed_tiles(&self, candidates: &FixedBitSet) -> FixedBitSet {
+        let columns = self.tile_columns();
+        let mut changed = FixedBitSet::with_capacity(candidates.len());
+        for tile in candidates.ones() {
+            let (rows, cols) = self.tile_span(tile, columns);
+            let differs = rows.into_iter().any(|row| {
+                let span = self.get_index(row, cols.start)..self.get_index(row, cols.end - 1) + 1;
+                let dying = !self.dying.is_empty() && self.dying[span.clone()] != self.old_dying[span.clone()];
+                dying || self.cells_differ(span)
+            });
+            if differs {
+                changed.insert(tile);
+            }
+        }
+        changed
+    }
+
+    /// Gets whether any cell in a span of indices is alive in one generation and
+    /// not the other, comparing a word of cells at a time.
+    fn cells_differ(&self, span: Range<usize>) -> bool {
+        let (new, old) = (self.cells.as_slice(), self.old_cells.as_slice());
+        let (first, last) = (span.start / 32, (span.end - 1) / 32);
+        (first..=last).any(|block| {
+            let low = if block == first { !0u32 << (span.start % 32) } else { !0 };
+            let high = if block == last { !0u32 >> (31 - (span.end - 1) % 32) } else { !0 };
+            (new[block] ^ old[block]) & low & high != 0
+        })
+    }
+
+    /// Copies the current generation of the dirty tiles into the old one.
+    ///
+    /// Every other tile is the same in both generations already: it did not
+    /// change last generation, and the generation before it was saved whole or
+    /// tile by tile in the same way. Edits mark the tiles they change as active,
+    /// so those are dirty as well.
+    fn save_tiles(&mut self, dirty: &FixedBitSet) {
+        let columns = self.tile_columns();
+        for tile in dirty.ones() {
+            let (rows, cols) = self.tile_span(tile, columns);
+            for row in rows {
+                let span = self.get_index(row, cols.start)..self.get_index(row, cols.end - 1) + 1;
+                copy_bits(self.cells.as_slice(), self.old_cells.as_mut_slice(), span.clone());
+                if !self.dying.is_empty() {
+                    self.old_dying[span.clone()].copy_from_slice(&self.dying[span.clone()]);
+                }
+                if !self.colors.is_empty() {
+                    self.old_colors[span.clone()].copy_from_slice(&self.colors[span]);
+                }
+            }
+        }
+    }
+
+    /// Gets how many tiles changed last generation.
+    pub fn active_tile_count(&self) -> usize {
+        self.active_tiles.count_ones(..)
+    }
+
+    /// Computes the next generation of the dirty tiles one cell at a time, every
+    /// other tile keeps the current generation.
+    fn next_generation_tiles(&mut self, dirty: &FixedBitSet) {
+        self.save_tiles(dirty);
+        let columns = self.tile_columns();
+        let mut active = FixedBitSet::with_capacity(dirty.len());
+        for tile in dirty.ones() {
+            let (rows, cols) = self.tile_span(tile, columns);
+            let range_counts = if self.rule.is_larger_than_life() {
+                Some(self.larger_than_life_counts(rows.clone(), cols.clone()))
+            } else {
+                None
+            };
+            for row in rows.clone() {
+                for col in cols.clone() {
+                    let idx = self.get_index(row, col);
+                    let offset = (row - rows.start) * cols.len() + col - cols.start;
+                    let range_count = range_counts.as_ref().map(|counts| counts[offset] as usize);
+                    let next = self.next_cell_state(row, col, range_count);
+                    if next != self.old_state(idx) {
+                        self.set_state(idx, next);
+                        active.insert(tile);
+                    }
+                }
+            }
+        }
+        self.active_tiles = active;
+    }
+
+    /// Computes the next generation into `cells` and `dying` one cell at a time.
+    ///
+    /// Large universes are split into bands of rows that are computed on separate
+    /// threads, each reading its halo rows from the shared old generation.
+    fn next_generation_scalar(&mut self) {
+        self.next_generation_banded(parallel::bands(self.height, parallel::MIN_BAND_CELLS / self.width));
+    }
+
+    /// Computes the next generation one cell at a time, each band of rows on its own task.
+    fn next_generation_banded(&mut self, bands: Vec<Range<usize>>) {
+        let range_counts = if self.rule.is_larger_than_life() {
+            Some(self.larger_than_life_counts(0..self.height, 0..self.width))
+        } else {
+            None
+        };
+        let universe = &*self;
+        let states = parallel::map_bands(bands, |rows| {
+            universe.next_band_states(rows, range_counts.as_deref())
+        });
+        for (idx, state) in states.into_iter().flatten().enumerate() {
+            self.set_state(idx, state);
+        }
+    }
+
+    /// Gets the next state of every cell in a band of rows, reading only the old generation.
+    fn next_band_states(&self, rows: Range<usize>, range_counts: Option<&[u16]>) -> Vec<u8> {
+        let mut states = Vec::with_capacity(rows.len() * self.width);
+        for row in rows {
+            for col in 0..self.width {
+                let range_count = range_counts.map(|counts| counts[self.get_index(row, col)] as usize);
+                states.push(self.next_cell_state(row, col, range_count));
+            }
+        }
+        states
     }
 
-    /// Toggle the value of a single cell in the universe between alive and dead.
-    pub fn toggle_cell(&mut self, row: usize, col: usize) {
+    /// Gets the next state of a cell from the old generation, using the precomputed
+    /// neighbor count of rules with a range, then applies the schedule and the noise.
+    /// Under multi-color rules live cells keep their color and newborn cells take
+    /// the majority color around them.
+    fn next_cell_state(&self, row: usize, col: usize, range_count: Option<usize>) -> u8 {
         let idx = self.get_index(row, col);
-        utils::log!("row = {}, col = {}, idx = {}, cap = {}", row, col, idx, self.cells.len());
-        self.cells.toggle(idx);
-        self.refresh_live_cell_list();
+        if let Some(obstacle) = self.obstacles.get(&idx) {
+            return obstacle.state(self.generation);
+        }
+        if !self.schedule.updates(self.generation, idx) {
+            return self.old_state(idx);
+        }
+        let next = self.next_uncolored_state(row, col, range_count);
+        if next != 1 || self.old_colors.is_empty() {
+            next
+        } else if self.old_cells[idx] {
+            self.old_state(idx)
+        } else {
+            1 + self.birth_color(row, col)
+        }
     }
 
-    /// Toggle the value of many cells in the universe between alive and dead.
-    pub fn toggle_cells(&mut self, cells: &[(usize, usize)]) {
-        for (row, col) in cells {
-            let idx = self.get_index(*row, *col);
-            self.cells.toggle(idx);
+    /// Gets the next state of a cell as its rule and the noise give it, 1 for any live cell.
+    /// Cyclic rules have no births or survivals for the noise to change, and the
+    /// noise takes the weighted sums of weighted rules as neighbor counts.
+    fn next_uncolored_state(&self, row: usize, col: usize, range_count: Option<usize>) -> u8 {
+        let idx = self.get_index(row, col);
+        let state = if self.old_cells[idx] { 1 } else { self.old_state(idx) };
+        let rule = self.rule_at(idx);
+        if rule.cyclic_threshold().is_some() {
+            return rule.next_cyclic_state(state, self.successor_count(row, col));
         }
-        self.refresh_live_cell_list();
+        let (live_neighbors, next) = match (rule.kernel(), range_count) {
+            (Some(kernel), _) => {
+                let sum = self.weighted_sum(row, col, kernel);
+                (sum.max(0) as usize, rule.next_weighted_state(state, sum))
+            }
+            (None, Some(count)) => (count, rule.next_state(state, count)),
+            (None, None) => {
+                let neighbors = self.neighbor_mask(row, col) & rule.neighbor_bits(row);
+                (neighbors.count_ones() as usize, rule.next_state_from_neighbors(state, neighbors))
+            }
+        };
+        if self.noise.is_deterministic() {
+            return next;
+        }
+        let born_or_survives = |alive| rule.transition(state, alive, alive);
+        self.noise.next_state(state, live_neighbors, next, born_or_survives, self.generation, idx)
     }
 
-    /// Set cells to be alive in a universe by passing the row and column
-    /// of each cell as an array.
-    // pub fn set_cells(&mut self, cells: &[(usize, usize)]) {
-    //     for (row, col) in cells.iter() {
-    //         let idx = self.get_index(*row, *col);
-    //         self.cells.set(idx, true);
-    //     }
-    // }
+    /// Updates the cells in `order` one at a time, each seeing the states given to
+    /// the cells before it this generation.
+    fn next_generation_in_place(&mut self, order: &[usize], dirty: &FixedBitSet) {
+        self.save_tiles(dirty);
+        let mut active = FixedBitSet::with_capacity(self.tile_columns() * self.tile_rows());
+        for idx in order.iter().copied() {
+            let (row, col) = (idx / self.width, idx % self.width);
+            let range_count = if self.rule.is_larger_than_life() {
+                Some(self.range_count(row, col))
+            } else {
+                None
+            };
+            let next = self.next_cell_state(row, col, range_count);
+            if next != self.old_state(idx) {
+                // The old generation doubles as the current one, so later cells see the change
+                self.set_state(idx, next);
+                self.set_old_state(idx, next);
+                active.insert(self.tile_of(idx));
+            }
+        }
+        self.active_tiles = active;
+    }
 
-    /// Set the width and height of the universe.
+    /// Computes the next generation straight into the blocks of `cells`, 64 cells at a time.
     ///
-    /// Resets all cells to the dead state.
-    pub fn set_size(&mut self, width: Option<usize>, height: Option<usize>) {
-        if let Some(w) = width {
-            self.width = w;
+    /// The word-wide step treats the universe as a torus, and its west and east
+    /// neighbors are only right away from the first and last column, so the
+    /// edges are recomputed one cell at a time afterwards.
+    fn next_generation_bitwise(&mut self) {
+        let words = bitwise::word_count(self.width * self.height);
+        let bands = parallel::bands(words, parallel::MIN_BAND_CELLS / 64);
+        let (width, height) = (self.width, self.height);
+        bitwise::next_generation(self.old_cells.as_slice(), self.cells.as_mut_slice(), width, height, &self.rule, bands);
+
+        let mut edges: Vec<(usize, usize)> = (0..self.height)
+            .flat_map(|row| [(row, 0), (row, self.width - 1)])
+            .collect();
+        if self.topology != Topology::Torus {
+            edges.extend((0..self.width).flat_map(|col| [(0, col), (self.height - 1, col)]));
         }
-        if let Some(h) = height {
-            self.height = h;
+        for (row, col) in edges {
+            let idx = self.get_index(row, col);
+            let alive = self.rule.next_state_from_neighbors(self.old_cells[idx] as u8, self.neighbor_mask(row, col)) == 1;
+            self.cells.set(idx, alive);
         }
-        if width != None || height != None {
-            self.reset_cells();
+    }
+
+    /// Gets the color most of the live cells around a cell have, the lowest of the
+    /// most common ones on a tie. Three different colors out of four give the
+    /// fourth, as in QuadLife.
+    fn birth_color(&self, row: usize, col: usize) -> u8 {
+        let species = self.rule.species() as usize;
+        let bits = self.rule_at(self.get_index(row, col)).neighbor_bits(row);
+        let mut counts = vec![0; species];
+        for (bit, (dr, dc)) in MOORE_OFFSETS.iter().enumerate() {
+            if bits >> bit & 1 == 0 {
+                continue;
+            }
+            if let Some((r, c)) = self.topology.resolve(row as isize + dr, col as isize + dc, self.width, self.height) {
+                let idx = self.get_index(r, c);
+                if self.old_cells[idx] {
+                    counts[self.old_colors[idx] as usize] += 1;
+                }
+            }
+        }
+        let most = counts.iter().copied().max().unwrap_or(0);
+        if species == 4 && most == 1 && counts.iter().sum::<usize>() == 3 {
+            return counts.iter().position(|count| *count == 0).unwrap() as u8;
+        }
+        counts.iter().position(|count| *count == most).unwrap_or(0) as u8
+    }
+
+    /// Moves every ant a cell forward after reading the cell under it, writing its
+    /// new color and turning as the turmite says. Ants facing the edge of a
+    /// bounded universe turn back instead of moving.
+    fn move_ants(&mut self) {
+        let Some(turmite) = self.turmite.take() else {
+            return;
+        };
+        let mut ants = std::mem::take(&mut self.ants);
+        for ant in ants.iter_mut() {
+            let idx = self.get_index(ant.row, ant.col);
+            let color = turmite.step(ant, self.state(idx));
+            self.set_state(idx, color);
+            self.active_tiles.insert(self.tile_of(idx));
+            let (dr, dc) = HEADINGS[ant.heading as usize];
+            match self.topology.resolve(ant.row as isize + dr, ant.col as isize + dc, self.width, self.height) {
+                Some((row, col)) => (ant.row, ant.col) = (row, col),
+                None => ant.heading = (ant.heading + 2) % 4,
+            }
+        }
+        self.ants = ants;
+        self.turmite = Some(turmite);
+    }
+
+    /// Puts every obstacle in its state for this generation, whichever way the
+    /// generation was computed or the ants went.
+    fn apply_obstacles(&mut self) {
+        let obstacles = std::mem::take(&mut self.obstacles);
+        for (idx, obstacle) in obstacles.iter() {
+            let state = obstacle.state(self.generation);
+            if self.state(*idx) != state {
+                self.set_state(*idx, state);
+                self.active_tiles.insert(self.tile_of(*idx));
+            }
+        }
+        self.obstacles = obstacles;
+    }
+
+    /// Gives every cell a random state, as cyclic rules need every state around to move.
+    fn fill_random_states(&mut self) {
+        let states = self.rule.states() as f64;
+        for idx in 0..self.width * self.height {
+            let state = (random::unit(0, self.generation, idx, CYCLIC_FILL_STREAM) * states) as u8;
+            self.set_state(idx, state);
         }
     }
 
+    /// Lists the live and dying cells of the active tiles again and gathers the
+    /// cells of every tile, then the obstacles and ants drawn over them in the
+    /// colors after the last state.
     fn refresh_live_cell_list(&mut self) {
-        self.live_cells.clear();
-        for row in 0..self.height {
-            for col in 0..self.width {
-                let idx = self.get_index(row, col);
-                if self.cells[idx] {
-                    self.live_cells.push((row as f32, col as f32));
+        let columns = self.tile_columns();
+        let mut tile_live_cells = std::mem::take(&mut self.tile_live_cells);
+        let tiles: Vec<usize> = if tile_live_cells.len() == self.active_tiles.len() {
+            self.active_tiles.ones().collect()
+        } else {
+            // The universe changed size, so every tile is listed
+            tile_live_cells = vec![Vec::new(); self.active_tiles.len()];
+            (0..tile_live_cells.len()).collect()
+        };
+        for tile in tiles {
+            let cells = &mut tile_live_cells[tile];
+            cells.clear();
+            let (rows, cols) = self.tile_span(tile, columns);
+            for row in rows {
+                for col in cols.clone() {
+                    let state = self.state(self.get_index(row, col));
+                    if state != 0 {
+                        cells.push((row as f32, col as f32, state as f32));
+                    }
                 }
             }
         }
+        self.live_cells.clear();
+        for cells in tile_live_cells.iter() {
+            self.live_cells.extend_from_slice(cells);
+        }
+        self.tile_live_cells = tile_live_cells;
+        let ant_state = self.state_count() as f32;
+        for (idx, obstacle) in self.obstacles.iter() {
+            let (row, col) = (idx / self.width, idx % self.width);
+            let kind = match obstacle {
+                Obstacle::Wall => 1.0,
+                Obstacle::Alive => 2.0,
+                Obstacle::Emitter { .. } => 3.0,
+            };
+            self.live_cells.push((row as f32, col as f32, ant_state + kind));
+        }
+        for ant in self.ants.iter() {
+            self.live_cells.push((ant.row as f32, ant.col as f32, ant_state));
+        }
     }
 
     fn reset_cells(&mut self) {
         let size = self.width * self.height;
         self.cells = FixedBitSet::with_capacity(size);
-        self.add_glider((self.width * self.width / 4) + (self.height / 4));
+        self.old_cells = FixedBitSet::with_capacity(size);
+        if !self.dying.is_empty() {
+            self.dying = vec![0; size];
+            self.old_dying = vec![0; size];
+        }
+        if !self.colors.is_empty() {
+            self.colors = vec![0; size];
+            self.old_colors = vec![0; size];
+        }
+        self.obstacles.clear();
+        self.clear_regions();
+        self.activate_all_tiles();
+        if self.width >= 4 && self.height >= 4 {
+            self.add_glider(self.get_index(self.height / 4, self.width / 4));
+        }
+        if let Some(ref turmite) = self.turmite {
+            self.ants = turmite.place_ants(self.width, self.height);
+        }
+        self.refresh_live_cell_list();
     }
 
     fn get_index(&self, row: usize, col: usize) -> usize {
-        (row * self.width + col) as usize
+        row * self.width + col
     }
 
-    fn live_neighbor_count(&self, row: usize, col: usize) -> u8 {
-        let mut count = 0;
-        let north = if row == 0 {
-            self.height - 1
-        } 
-        else {
-            row - 1
-        };
-        let south = if row == self.height - 1 {
+    /// Gets the state of a cell: 0 when dead, 1 when alive and 2 or more while dying.
+    /// Multi-color rules have no dying cells, a live cell is 1 plus its color.
+    fn state(&self, idx: usize) -> u8 {
+        if self.cells[idx] {
+            1 + self.colors.get(idx).copied().unwrap_or(0)
+        } else if self.dying.is_empty() {
             0
         } else {
-            row + 1
-        };
-        let west = if col == 0 {
-            self.width - 1
-        } else {
-            col - 1
-        };
-        let east = if col == self.width - 1 {
+            self.dying[idx]
+        }
+    }
+
+    fn old_state(&self, idx: usize) -> u8 {
+        if self.old_cells[idx] {
+            1 + self.old_colors.get(idx).copied().unwrap_or(0)
+        } else if self.old_dying.is_empty() {
             0
         } else {
-            col + 1
+            self.old_dying[idx]
+        }
+    }
+
+    fn set_state(&mut self, idx: usize, state: u8) {
+        if !self.colors.is_empty() {
+            self.cells.set(idx, state != 0);
+            self.colors[idx] = state.saturating_sub(1);
+            return;
+        }
+        self.cells.set(idx, state == 1);
+        if !self.dying.is_empty() {
+            self.dying[idx] = if state > 1 { state } else { 0 };
+        }
+    }
+
+    fn set_old_state(&mut self, idx: usize, state: u8) {
+        if !self.old_colors.is_empty() {
+            self.old_cells.set(idx, state != 0);
+            self.old_colors[idx] = state.saturating_sub(1);
+            return;
+        }
+        self.old_cells.set(idx, state == 1);
+        if !self.old_dying.is_empty() {
+            self.old_dying[idx] = if state > 1 { state } else { 0 };
+        }
+    }
+
+    /// Under multi-color rules a cell steps through the colors before going back to
+    /// dead, and under cyclic rules through every state. Obstacles keep their state.
+    fn toggle_index(&mut self, idx: usize) {
+        if self.obstacles.contains_key(&idx) {
+            return;
+        }
+        let state = match self.state(idx) {
+            s if self.rule.cyclic_threshold().is_some() => self.rule.successor(s),
+            0 => 1,
+            s if s < self.rule.species() => s + 1,
+            _ => 0,
         };
-        let nw = self.get_index(north, west);
-        count += self.old_cells[nw] as u8;
-        let n = self.get_index(north, col);
-        count += self.old_cells[n] as u8;
-        let ne = self.get_index(north, east);
-        count += self.old_cells[ne] as u8;
-        let w = self.get_index(row, west);
-        count += self.old_cells[w] as u8;
-        let e = self.get_index(row, east);
-        count += self.old_cells[e] as u8;
-        let sw = self.get_index(south, west);
-        count += self.old_cells[sw] as u8;
-        let s = self.get_index(south, col);
-        count += self.old_cells[s] as u8;
-        let se = self.get_index(south, east);
-        count += self.old_cells[se] as u8;
-        
+        self.set_state(idx, state);
+        self.active_tiles.insert(self.tile_of(idx));
+    }
+
+    /// Gets whether the cell at a position was alive last generation, following
+    /// the topology for positions past the edges of the universe.
+    fn old_alive_at(&self, row: isize, col: isize) -> bool {
+        match self.topology.resolve(row, col, self.width, self.height) {
+            Some((r, c)) => self.old_cells[self.get_index(r, c)],
+            None => false,
+        }
+    }
+
+    /// Gets which of the eight cells around a cell were alive last generation,
+    /// bit `i` is the neighbor at `MOORE_OFFSETS[i]` as `hensel` expects.
+    fn neighbor_mask(&self, row: usize, col: usize) -> u8 {
+        let (row, col) = (row as isize, col as isize);
+        let mut mask = 0;
+        for (bit, (dr, dc)) in MOORE_OFFSETS.iter().enumerate() {
+            mask |= (self.old_alive_at(row + dr, col + dc) as u8) << bit;
+        }
+        mask
+    }
+
+    /// Gets how many cells within range of a cell were one state ahead of it last
+    /// generation, for cyclic rules.
+    fn successor_count(&self, row: usize, col: usize) -> usize {
+        let successor = self.rule.successor(self.old_state(self.get_index(row, col)));
+        let r = self.rule.range() as isize;
+        let diamond = self.rule.neighborhood() == Neighborhood::VonNeumann;
+        let (row, col) = (row as isize, col as isize);
+        let mut count = 0;
+        for dy in -r..=r {
+            for dx in -r..=r {
+                if (dx == 0 && dy == 0) || (diamond && dx.abs() + dy.abs() > r) {
+                    continue;
+                }
+                if let Some((other_row, other_col)) = self.topology.resolve(row + dy, col + dx, self.width, self.height) {
+                    count += (self.old_state(self.get_index(other_row, other_col)) == successor) as usize;
+                }
+            }
+        }
+        count
+    }
+
+    /// Adds up the weights of a kernel over the cells around a cell that were alive
+    /// last generation, for weighted rules.
+    fn weighted_sum(&self, row: usize, col: usize, kernel: &Kernel) -> i32 {
+        let (row, col) = (row as isize, col as isize);
+        kernel.offsets()
+            .filter(|(dy, dx, _)| self.old_alive_at(row + dy, col + dx))
+            .map(|(_, _, weight)| weight)
+            .sum()
+    }
+
+    /// Counts the live cells within the rule's range of one cell in the old
+    /// generation, as `larger_than_life_counts` does for every cell at once.
+    fn range_count(&self, row: usize, col: usize) -> usize {
+        let r = self.rule.range() as isize;
+        let diamond = self.rule.neighborhood() == Neighborhood::VonNeumann;
+        let (row, col) = (row as isize, col as isize);
+        let mut count = 0;
+        for dy in -r..=r {
+            for dx in -r..=r {
+                let outside = diamond && dx.abs() + dy.abs() > r;
+                let center = dx == 0 && dy == 0 && !self.rule.include_center();
+                if !outside && !center {
+                    count += self.old_alive_at(row + dy, col + dx) as usize;
+                }
+            }
+        }
         count
     }
 
+    /// Counts the live cells in the neighborhood of every cell in `rows` and `cols`
+    /// for rules with a range, row major over those cells.
+    ///
+    /// The cells are padded by the range on every side following the topology, and a
+    /// summed-area table of the padded cells turns each square neighborhood into
+    /// four lookups. Diamond shaped neighborhoods sum one row span per row.
+    fn larger_than_life_counts(&self, rows: Range<usize>, cols: Range<usize>) -> Vec<u16> {
+        let r = self.rule.range();
+        let padded_width = cols.len() + 2 * r;
+        let padded_height = rows.len() + 2 * r;
+        let stride = padded_width + 1;
+        let mut table = vec![0u32; (padded_height + 1) * stride];
+        for y in 0..padded_height {
+            let row = (rows.start + y) as isize - r as isize;
+            let mut row_sum = 0;
+            for x in 0..padded_width {
+                let col = (cols.start + x) as isize - r as isize;
+                row_sum += self.old_alive_at(row, col) as u32;
+                table[(y + 1) * stride + x + 1] = table[y * stride + x + 1] + row_sum;
+            }
+        }
+        // Sum of the padded cells in rows top..bottom and columns left..right, exclusive
+        let area = |top: usize, left: usize, bottom: usize, right: usize| {
+            table[bottom * stride + right] + table[top * stride + left]
+                - table[top * stride + right] - table[bottom * stride + left]
+        };
+
+        let mut counts = vec![0u16; rows.len() * cols.len()];
+        for (y, row) in rows.clone().enumerate() {
+            for (x, col) in cols.clone().enumerate() {
+                // The cell sits at (y + r, x + r) in the padded cells
+                // Larger than Life rules are read with either the square or the diamond
+                let mut count = match self.rule.neighborhood() {
+                    Neighborhood::VonNeumann => (0..=2 * r).map(|dy| {
+                        let reach = r - (dy as isize - r as isize).unsigned_abs();
+                        area(y + dy, x + r - reach, y + dy + 1, x + r + reach + 1)
+                    }).sum(),
+                    _ => area(y, x, y + 2 * r + 1, x + 2 * r + 1),
+                };
+                if !self.rule.include_center() {
+                    count -= self.old_cells[self.get_index(row, col)] as u32;
+                }
+                counts[y * cols.len() + x] = count as u16;
+            }
+        }
+        counts
+    }
+
     fn add_glider(&mut self, idx: usize) {
         self.cells.set(idx, true);
         self.cells.set(idx + 1, true);
@@ -178,3 +871,705 @@ impl Universe {
         self.cells.set(idx + self.width - 1, true);
     }
 }
+
+/// Copies the bits in a span of indices from one bitset's words to another's,
+/// leaving the bits around the span as they are.
+fn copy_bits(from: &[u32], to: &mut [u32], span: Range<usize>) {
+    let (first, last) = (span.start / 32, (span.end - 1) / 32);
+    for block in first..=last {
+        let low = if block == first { !0u32 << (span.start % 32) } else { !0 };
+        let high = if block == last { !0u32 >> (31 - (span.end - 1) % 32) } else { !0 };
+        let mask = low & high;
+        to[block] = (to[block] & !mask) | (from[block] & mask);
+    }
+}
+
+impl Simulation for Universe {
+    fn width(&self) -> usize {
+        self.width
+    }
+
+    fn height(&self) -> usize {
+        self.height
+    }
+
+    fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    /// When less than half of the universe can change only those tiles are
+    /// computed. Otherwise two state rules on the eight cell neighborhood use
+    /// the word-wide bitwise step, unless regions run other rules, and every
+    /// other rule goes cell by cell. With noise or an asynchronous schedule any
+    /// cell can change and every cell goes cell by cell, in place when the
+    /// schedule gives an order. Ants move once the grid has its next generation,
+    /// and obstacles are put back after them.
+    fn tick(&mut self) {
+        // let _timer = utils::Timer::new("Universe::tick");
+        self.generation += 1;
+        let deterministic = self.noise.is_deterministic() && self.schedule == Schedule::Synchronous;
+        let dirty = self.dirty_tiles();
+        if let Some(order) = self.schedule.in_place_order(self.width * self.height, self.generation) {
+            self.next_generation_in_place(&order, &dirty);
+        } else if deterministic && dirty.count_ones(..) * 2 < dirty.len() {
+            self.next_generation_tiles(&dirty);
+        } else {
+            // The current generation becomes the old one and its buffer is overwritten
+            std::mem::swap(&mut self.cells, &mut self.old_cells);
+            std::mem::swap(&mut self.dying, &mut self.old_dying);
+            std::mem::swap(&mut self.colors, &mut self.old_colors);
+            if deterministic && self.rule.is_life_like() && self.region_rules.is_empty() {
+                self.next_generation_bitwise();
+            } else {
+                self.next_generation_scalar();
+            }
+            // Without noise or a schedule only the dirty tiles can have changed
+            let candidates = if deterministic {
+                dirty
+            } else {
+                let mut all = FixedBitSet::with_capacity(dirty.len());
+                all.insert_range(..);
+                all
+            };
+            self.active_tiles = self.changed_tiles(&candidates);
+        }
+        self.move_ants();
+        self.apply_obstacles();
+        self.refresh_live_cell_list();
+    }
+
+    fn rule(&self) -> String {
+        self.rule.to_string()
+    }
+
+    /// Live cells are left as they are so a pattern can be continued under the new rule,
+    /// dying cells are cleared when the number of states changes and live cells take
+    /// the first color when the number of colors does. Switching to a cyclic rule
+    /// fills the universe with random states instead. Painted regions keep their
+    /// rules, which have to fit the new rule.
+    fn set_rule(&mut self, rule: &str) -> Result<(), String> {
+        let rule = Rule::parse(rule)?;
+        for region_rule in self.region_rules.iter() {
+            Universe::check_region_rule(&rule, region_rule)?;
+        }
+        let states = rule.states().max(rule.species() + 1) as usize;
+        if let Some(turmite) = self.turmite.as_ref().filter(|turmite| turmite.colors() > states) {
+            return Err(format!("the turmite has {} colors and the rule only {} states", turmite.colors(), states));
+        }
+        let cyclic = rule.cyclic_threshold().is_some() && self.rule.cyclic_threshold().is_none();
+        if rule.species() != self.rule.species() {
+            self.colors = if rule.species() > 1 {
+                vec![0; self.width * self.height]
+            } else {
+                Vec::new()
+            };
+            self.old_colors = self.colors.clone();
+        }
+        if rule.states() != self.rule.states() {
+            self.dying = if rule.states() > 2 {
+                vec![0; self.width * self.height]
+            } else {
+                Vec::new()
+            };
+            self.old_dying = self.dying.clone();
+        }
+        self.rule = rule;
+        if cyclic {
+            self.fill_random_states();
+        }
+        self.activate_all_tiles();
+        self.refresh_live_cell_list();
+        Ok(())
+    }
+
+    /// Multi-color rules have a state for every color.
+    fn state_count(&self) -> u8 {
+        self.rule.states().max(self.rule.species() + 1)
+    }
+
+    /// Cyclic rules go around the color wheel, and ants and obstacles come after the last state.
+    fn palette(&self) -> Vec<[f32; 3]> {
+        let mut palette = if self.rule.cyclic_threshold().is_some() {
+            simulation::cyclic_palette(self.state_count())
+        } else if self.rule.species() == 1 {
+            simulation::default_palette(self.state_count())
+        } else {
+            let mut palette = vec![[0.3, 0.3, 0.3]];
+            palette.extend_from_slice(&SPECIES_COLORS[..self.rule.species() as usize]);
+            palette
+        };
+        palette.push(ANT_COLOR);
+        palette.extend_from_slice(&OBSTACLE_COLORS);
+        palette
+    }
+
+    /// Gets an array with row, column and state values for every live or dying cell in the universe.
+    fn get_live_cells(&self) -> &[(f32,f32,f32)] {
+        &self.live_cells
+    }
+
+    /// A dying cell is toggled to dead.
+    fn toggle_cell(&mut self, row: usize, col: usize) {
+        let idx = self.get_index(row, col);
+        utils::log!("row = {}, col = {}, idx = {}, cap = {}", row, col, idx, self.cells.len());
+        self.toggle_index(idx);
+        self.refresh_live_cell_list();
+    }
+
+    fn toggle_cells(&mut self, cells: &[(usize, usize)]) {
+        for (row, col) in cells {
+            let idx = self.get_index(*row, *col);
+            self.toggle_index(idx);
+        }
+        self.refresh_live_cell_list();
+    }
+
+    fn set_cells(&mut self, cells: &[(usize, usize)]) {
+        self.cells.clear();
+        self.dying.iter_mut().for_each(|state| *state = 0);
+        self.colors.iter_mut().for_each(|color| *color = 0);
+        for (row, col) in cells.iter() {
+            let idx = self.get_index(*row, *col);
+            self.cells.set(idx, true);
+        }
+        self.apply_obstacles();
+        self.activate_all_tiles();
+        self.refresh_live_cell_list();
+    }
+
+    fn set_size(&mut self, width: Option<usize>, height: Option<usize>) {
+        if let Some(w) = width {
+            self.width = w;
+        }
+        if let Some(h) = height {
+            self.height = h;
+        }
+        if width.is_some() || height.is_some() {
+            self.generation = 0;
+            self.reset_cells();
+        }
+    }
+
+    fn active_tiles(&self) -> Option<usize> {
+        Some(self.active_tile_count())
+    }
+
+    fn hexagonal(&self) -> bool {
+        self.rule.neighborhood() == Neighborhood::Hexagonal
+    }
+
+    fn region_edges(&self) -> Option<&[[f32; 4]]> {
+        Some(&self.region_edges)
+    }
+
+    fn species_populations(&self) -> Option<Vec<usize>> {
+        if self.colors.is_empty() {
+            return None;
+        }
+        let mut populations = vec![0; self.rule.species() as usize];
+        for idx in self.cells.ones() {
+            populations[self.colors[idx] as usize] += 1;
+        }
+        Some(populations)
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Universe;
+    use crate::noise::Noise;
+    use crate::obstacle::Obstacle;
+    use crate::parallel;
+    use crate::random;
+    use crate::schedule::Schedule;
+    use crate::simulation::Simulation;
+    use crate::topology::Topology;
+    use crate::turmite::Turmite;
+
+    /// Fills a universe with a repeatable soup of live cells.
+    fn soup(width: usize, height: usize) -> Universe {
+        let mut universe = Universe::new(width, height);
+        let cells: Vec<(usize, usize)> = (0..width * height)
+            .filter(|idx| random::unit(12345, 0, *idx, 0) < 0.3)
+            .map(|idx| (idx / width, idx % width))
+            .collect();
+        universe.toggle_cells(&cells);
+        universe
+    }
+
+    #[test]
+    fn range_one_larger_than_life_matches_life() {
+        let mut life = soup(40, 30);
+        let mut ltl = soup(40, 30);
+        ltl.set_rule("R1,C0,M0,S2..3,B3..3,NM").unwrap();
+        for _ in 0..20 {
+            life.tick();
+            ltl.tick();
+            assert_eq!(life.get_live_cells(), ltl.get_live_cells());
+        }
+    }
+
+    #[test]
+    fn rectangular_universe_wraps_on_both_axes() {
+        let mut universe = Universe::new(96, 12);
+        // A glider returns to its shape shifted one cell diagonally every 4 generations,
+        // after 4 * 96 generations it has wrapped around both axes
+        let start = universe.get_live_cells().to_vec();
+        for _ in 0..(4 * 96) {
+            universe.tick();
+        }
+        assert_eq!(universe.get_live_cells(), &start[..]);
+    }
+
+    #[test]
+    fn starts_empty_when_too_small_for_a_glider() {
+        for (width, height) in [(1, 1), (1920, 3), (3, 10)] {
+            let mut universe = Universe::new(width, height);
+            assert!(universe.get_live_cells().is_empty());
+            universe.tick();
+            universe.set_size(Some(2), None);
+            assert!(universe.get_live_cells().is_empty());
+        }
+        assert_eq!(Universe::new(4, 4).get_live_cells().len(), 5);
+    }
+
+    #[test]
+    fn bitwise_tick_matches_scalar_tick() {
+        let sizes = [(40, 30), (64, 8), (63, 17), (65, 4), (4, 50), (130, 70), (5, 5)];
+        let rules = ["B3/S23", "B36/S23", "B2/S", "B3678/S34678", "B1/S012345678"];
+        let topologies = [Topology::Torus, Topology::Plane, Topology::KleinBottle, Topology::CrossSurface];
+        for (width, height) in sizes {
+            for rule in rules {
+                for topology in topologies {
+                    let mut scalar = soup(width, height);
+                    scalar.set_rule(rule).unwrap();
+                    scalar.set_topology(topology);
+                    let mut bitwise = soup(width, height);
+                    bitwise.set_rule(rule).unwrap();
+                    bitwise.set_topology(topology);
+                    for _ in 0..12 {
+                        std::mem::swap(&mut scalar.cells, &mut scalar.old_cells);
+                        scalar.next_generation_scalar();
+                        std::mem::swap(&mut bitwise.cells, &mut bitwise.old_cells);
+                        bitwise.next_generation_bitwise();
+                        assert_eq!(scalar.cells, bitwise.cells, "{}x{} {} {}", width, height, rule, topology);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn banded_tick_matches_single_band() {
+        for rule in ["B2/S345/C4", "B3/S23"] {
+            let mut banded = soup(60, 40);
+            banded.set_rule(rule).unwrap();
+            for _ in 0..5 {
+                std::mem::swap(&mut banded.cells, &mut banded.old_cells);
+                std::mem::swap(&mut banded.dying, &mut banded.old_dying);
+                let serial = banded.next_band_states(0..40, None);
+                // Seven bands of uneven length, whatever the thread count
+                banded.next_generation_banded(parallel::split(40, 7));
+                let states: Vec<u8> = (0..60 * 40).map(|idx| banded.state(idx)).collect();
+                assert_eq!(serial, states);
+            }
+        }
+    }
+
+    #[test]
+    fn active_tiles_tick_matches_full_tick() {
+        let rules = ["B3/S23", "B2/S345/C4", "R2,C0,M0,S3..6,B4..5,NM", "QuadLife"];
+        let topologies = [Topology::Torus, Topology::KleinBottle];
+        for rule in rules {
+            for topology in topologies {
+                // A small soup in one corner of a large universe leaves most tiles still
+                let mut tiled = Universe::new(320, 256);
+                let mut full = Universe::new(320, 256);
+                let cells: Vec<_> = soup(40, 30).cells.ones().map(|idx| (100 + idx / 40, 150 + idx % 40)).collect();
+                for universe in [&mut tiled, &mut full] {
+                    universe.set_rule(rule).unwrap();
+                    universe.set_topology(topology);
+                    universe.set_cells(&cells);
+                    if universe.rule.species() > 1 {
+                        // Every third cell takes the second color and every fifth the third
+                        universe.toggle_cells(&cells.iter().copied().step_by(3).collect::<Vec<_>>());
+                        universe.toggle_cells(&cells.iter().copied().step_by(5).collect::<Vec<_>>());
+                    }
+                }
+                for _ in 0..30 {
+                    tiled.tick();
+                    assert!(tiled.active_tile_count() < 40);
+                    full.generation += 1;
+                    std::mem::swap(&mut full.cells, &mut full.old_cells);
+                    std::mem::swap(&mut full.dying, &mut full.old_dying);
+                    std::mem::swap(&mut full.colors, &mut full.old_colors);
+                    full.next_generation_scalar();
+                    full.activate_all_tiles();
+                    full.refresh_live_cell_list();
+                    assert_eq!(tiled.get_live_cells(), full.get_live_cells(), "{} {}", rule, topology);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn still_tiles_keep_old_generation_in_step() {
+        for schedule in ["sync", "sweep"] {
+            let mut universe = Universe::new(320, 256);
+            universe.set_rule("B2/S345/C4").unwrap();
+            universe.set_schedule(Schedule::parse(schedule).unwrap());
+            let cells: Vec<_> = soup(40, 30).cells.ones().map(|idx| (100 + idx / 40, 150 + idx % 40)).collect();
+            universe.set_cells(&cells);
+            for generation in 0..20 {
+                if generation == 10 {
+                    universe.toggle_cells(&[(20, 20), (20, 21), (21, 20)]);
+                }
+                universe.tick();
+                // Only tiles that changed this generation differ from the last one
+                for idx in 0..320 * 256 {
+                    if !universe.active_tiles.contains(universe.tile_of(idx)) {
+                        assert_eq!(universe.state(idx), universe.old_state(idx), "{} {}", schedule, generation);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn still_life_has_no_active_tiles() {
+        let mut universe = Universe::new(100, 100);
+        universe.set_cells(&[(50, 50), (50, 51), (51, 50), (51, 51)]);
+        universe.tick();
+        assert_eq!(universe.active_tile_count(), 0);
+        universe.tick();
+        assert_eq!(universe.get_live_cells().len(), 4);
+        // A glider only keeps the tiles it passes through active
+        universe.set_cells(&[(10, 10), (10, 11), (9, 10), (9, 9), (11, 9)]);
+        for _ in 0..8 {
+            universe.tick();
+        }
+        assert!(universe.active_tile_count() <= 4);
+    }
+
+    #[test]
+    fn isotropic_rule_evolves_mirrored_soup_mirrored() {
+        let original = soup(30, 30);
+        let mut universe = Universe::new(30, 30);
+        let mut transposed = Universe::new(30, 30);
+        universe.set_cells(&original.cells.ones().map(|idx| (idx / 30, idx % 30)).collect::<Vec<_>>());
+        transposed.set_cells(&original.cells.ones().map(|idx| (idx % 30, idx / 30)).collect::<Vec<_>>());
+        for rule in ["B2-a/S12", "B3/S2-i34q"] {
+            universe.set_rule(rule).unwrap();
+            transposed.set_rule(rule).unwrap();
+            for _ in 0..10 {
+                universe.tick();
+                transposed.tick();
+                let mut cells: Vec<_> = transposed.get_live_cells().iter().map(|(row, col, _)| (*col, *row)).collect();
+                cells.sort_by(|a, b| a.partial_cmp(b).unwrap());
+                let expected: Vec<_> = universe.get_live_cells().iter().map(|(row, col, _)| (*row, *col)).collect();
+                assert_eq!(cells, expected, "{}", rule);
+            }
+        }
+    }
+
+    #[test]
+    fn von_neumann_and_hexagonal_neighbors() {
+        let mut universe = Universe::new(20, 20);
+        // A single cell under B1/S with four neighbors grows into a diamond, with six into a hexagon
+        universe.set_rule("B1/SV").unwrap();
+        universe.set_cells(&[(10, 10)]);
+        universe.tick();
+        let cells: Vec<_> = universe.get_live_cells().iter().map(|(row, col, _)| (*row as usize, *col as usize)).collect();
+        assert_eq!(cells, [(9, 10), (10, 9), (10, 11), (11, 10)]);
+
+        universe.set_rule("B1/SH").unwrap();
+        for row in [10, 11] {
+            universe.set_cells(&[(row, 10)]);
+            universe.tick();
+            let cells: Vec<_> = universe.get_live_cells().iter().map(|(row, col, _)| (*row as usize, *col as usize)).collect();
+            // Odd rows sit half a cell right, so their neighbors above and below shift right as well
+            let shift = row % 2;
+            assert_eq!(cells, [
+                (row - 1, 9 + shift), (row - 1, 10 + shift),
+                (row, 9), (row, 11),
+                (row + 1, 9 + shift), (row + 1, 10 + shift),
+            ]);
+        }
+    }
+
+    #[test]
+    fn live_cells_are_listed_again_only_where_tiles_change() {
+        let mut universe = Universe::new(200, 150);
+        universe.set_rule("B2/S345/C4").unwrap();
+        let cells: Vec<_> = soup(30, 20).cells.ones().map(|idx| (60 + idx / 30, 90 + idx % 30)).collect();
+        universe.set_cells(&cells);
+        for _ in 0..20 {
+            universe.tick();
+            let mut listed = universe.get_live_cells().to_vec();
+            listed.sort_by(|a, b| a.partial_cmp(b).unwrap());
+            let all: Vec<_> = (0..200 * 150)
+                .filter(|idx| universe.state(*idx) != 0)
+                .map(|idx| ((idx / 200) as f32, (idx % 200) as f32, universe.state(idx) as f32))
+                .collect();
+            assert_eq!(listed, all);
+        }
+    }
+
+    #[test]
+    fn plane_topology_does_not_wrap() {
+        let mut universe = Universe::new(20, 20);
+        universe.set_topology(Topology::Plane);
+        // The glider runs into the top border and turns into a block there
+        for _ in 0..100 {
+            universe.tick();
+        }
+        let cells: Vec<_> = universe.get_live_cells().to_vec();
+        assert_eq!(cells.len(), 4);
+        assert!(cells.iter().all(|(row, _, _)| *row <= 1.0));
+    }
+
+    #[test]
+    fn larger_than_life_counts_match_naive_counts() {
+        for rule in ["R3,C0,M1,S1..1,B1..1,NM", "R2,C0,M0,S1..1,B1..1,NN"] {
+            let mut universe = soup(23, 17);
+            universe.set_rule(rule).unwrap();
+            universe.old_cells.clone_from(&universe.cells);
+            let counts = universe.larger_than_life_counts(0..17, 0..23);
+            let r = universe.rule.range() as isize;
+            let diamond = rule.ends_with("NN");
+            for row in 0..17isize {
+                for col in 0..23isize {
+                    let mut count = 0;
+                    for dy in -r..=r {
+                        for dx in -r..=r {
+                            let skip_center = dx == 0 && dy == 0 && !universe.rule.include_center();
+                            if skip_center || (diamond && dx.abs() + dy.abs() > r) {
+                                continue;
+                            }
+                            let idx = universe.get_index((row + dy).rem_euclid(17) as usize, (col + dx).rem_euclid(23) as usize);
+                            count += universe.cells[idx] as u16;
+                        }
+                    }
+                    assert_eq!(counts[universe.get_index(row as usize, col as usize)], count);
+                    assert_eq!(universe.range_count(row as usize, col as usize), count as usize);
+                }
+            }
+            // Counting part of the universe gives the same counts, wrapping at the edges
+            let part = universe.larger_than_life_counts(10..17, 15..23);
+            for (i, count) in part.iter().enumerate() {
+                assert_eq!(*count, counts[universe.get_index(10 + i / 8, 15 + i % 8)]);
+            }
+        }
+    }
+
+    #[test]
+    fn noise_is_repeatable_from_a_seed() {
+        let run = |rule: &str, noise: &str| {
+            let mut universe = soup(40, 30);
+            universe.set_rule(rule).unwrap();
+            universe.set_noise(Noise::parse(noise).unwrap());
+            for _ in 0..10 {
+                universe.tick();
+            }
+            universe.get_live_cells().to_vec()
+        };
+        let seeded = run("B3/S23", "B2=0.1 S3=0.5 T=0.01 seed=1");
+        assert_eq!(seeded, run("B3/S23", "B2=0.1 S3=0.5 T=0.01 seed=1"));
+        assert_ne!(seeded, run("B3/S23", "B2=0.1 S3=0.5 T=0.01 seed=2"));
+        // Certain births and survivals are the rule itself
+        assert_eq!(run("B3/S23", "B3=1 S2=1 S3=1 seed=5"), run("B3/S23", ""));
+        // Flipping every cell after a rule that kills everything fills the universe
+        assert_eq!(run("B/S", "T=1").len(), 40 * 30);
+    }
+
+    #[test]
+    fn schedules_are_repeatable_from_a_seed() {
+        let run = |rule: &str, schedule: &str| {
+            let mut universe = soup(40, 30);
+            universe.set_rule(rule).unwrap();
+            universe.set_schedule(Schedule::parse(schedule).unwrap());
+            for _ in 0..10 {
+                universe.tick();
+            }
+            universe.get_live_cells().to_vec()
+        };
+        for rule in ["B3/S23", "B2/S/C4", "R2,C0,M0,S3..5,B4..5,NM"] {
+            let synchronous = run(rule, "sync");
+            assert_eq!(run(rule, "alpha=1 seed=3"), synchronous, "{}", rule);
+            for schedule in ["sweep", "random seed=1", "alpha=0.5 seed=1"] {
+                let scheduled = run(rule, schedule);
+                assert_ne!(scheduled, synchronous, "{} {}", rule, schedule);
+                assert_eq!(run(rule, schedule), scheduled, "{} {}", rule, schedule);
+            }
+            assert_ne!(run(rule, "random seed=2"), run(rule, "random seed=1"), "{}", rule);
+        }
+    }
+
+    #[test]
+    fn species_births_take_the_majority_color() {
+        for (rule, colors, born) in [("Immigration", [1, 1, 2], 1.0), ("QuadLife", [1, 2, 3], 4.0), ("QuadLife", [3, 2, 3], 3.0)] {
+            let mut universe = Universe::new(12, 12);
+            universe.set_rule(rule).unwrap();
+            universe.set_cells(&[]);
+            // A row of three cells, each toggled up to its color
+            for (col, color) in (4..7).zip(colors) {
+                for _ in 0..color {
+                    universe.toggle_cells(&[(5, col)]);
+                }
+            }
+            universe.tick();
+            let expected = [(4.0, 5.0, born), (5.0, 5.0, colors[1] as f32), (6.0, 5.0, born)];
+            assert_eq!(universe.get_live_cells(), &expected[..], "{} {:?}", rule, colors);
+        }
+    }
+
+    #[test]
+    fn species_rules_run_the_same_cells_as_their_rule() {
+        let mut life = soup(40, 30);
+        let mut quad = soup(40, 30);
+        quad.set_rule("QuadLife").unwrap();
+        let cells: Vec<(usize, usize)> = quad.get_live_cells().iter()
+            .map(|(row, col, _)| (*row as usize, *col as usize))
+            .collect();
+        // Give every third live cell the second color
+        quad.toggle_cells(&cells.iter().copied().step_by(3).collect::<Vec<_>>());
+        for _ in 0..20 {
+            life.tick();
+            quad.tick();
+            let alive: Vec<(f32, f32)> = quad.get_live_cells().iter().map(|(row, col, _)| (*row, *col)).collect();
+            let expected: Vec<(f32, f32)> = life.get_live_cells().iter().map(|(row, col, _)| (*row, *col)).collect();
+            assert_eq!(alive, expected);
+        }
+        let populations = quad.species_populations().unwrap();
+        assert_eq!(populations.iter().sum::<usize>(), life.get_live_cells().len());
+        assert!(populations[0] > 0 && populations[1] > 0);
+        assert_eq!(life.species_populations(), None);
+    }
+
+    #[test]
+    fn langtons_ant_walks_a_square_on_a_still_grid() {
+        let mut universe = Universe::new(64, 64);
+        universe.set_rule("B/S012345678").unwrap();
+        universe.set_cells(&[]);
+        universe.set_turmite(Some(Turmite::parse("RL").unwrap())).unwrap();
+        for _ in 0..4 {
+            universe.tick();
+        }
+        // Four right turns leave a square of live cells and the ant back where it started
+        let expected = [(32.0, 32.0, 1.0), (32.0, 33.0, 1.0), (33.0, 32.0, 1.0), (33.0, 33.0, 1.0), (32.0, 32.0, 2.0)];
+        assert_eq!(universe.get_live_cells(), &expected[..]);
+        for _ in 0..500 {
+            universe.tick();
+        }
+        assert!(universe.active_tile_count() <= 4);
+        assert!(universe.set_turmite(Some(Turmite::parse("RLR").unwrap())).is_err());
+        universe.set_turmite(None).unwrap();
+        assert!(universe.get_live_cells().iter().all(|(_, _, state)| *state == 1.0));
+    }
+
+    #[test]
+    fn cyclic_cells_move_on_to_the_state_around_them() {
+        let mut universe = Universe::new(10, 10);
+        universe.set_rule("R1/T1/C3/NM").unwrap();
+        // Switching to a cyclic rule fills the universe with every state
+        let states: Vec<f32> = universe.get_live_cells().iter().map(|(_, _, state)| *state).collect();
+        assert!(states.contains(&1.0) && states.contains(&2.0) && states.len() < 100);
+        universe.set_cells(&[(5, 5)]);
+        universe.tick();
+        assert_eq!(universe.get_live_cells().len(), 9);
+        assert!(universe.get_live_cells().iter().all(|(_, _, state)| *state == 1.0));
+        universe.toggle_cells(&[(5, 5)]);
+        universe.tick();
+        let advanced = universe.get_live_cells().iter().filter(|(_, _, state)| *state == 2.0).count();
+        assert_eq!(advanced, 9);
+        assert!(universe.set_turmite(Some(Turmite::parse("RLR").unwrap())).is_ok());
+        assert!(universe.set_rule("B3/S23").is_err());
+    }
+
+    #[test]
+    fn obstacles_ignore_the_rule() {
+        let mut universe = Universe::new(12, 12);
+        universe.set_cells(&[(5, 4), (5, 5), (5, 6)]);
+        universe.toggle_obstacle(4, 5, Obstacle::Wall);
+        universe.toggle_obstacle(9, 9, Obstacle::Alive);
+        universe.tick();
+        // The blinker cannot turn into the wall, and the lone cell does not die
+        let expected = [(5.0, 5.0, 1.0), (6.0, 5.0, 1.0), (9.0, 9.0, 1.0), (4.0, 5.0, 3.0), (9.0, 9.0, 4.0)];
+        assert_eq!(universe.get_live_cells(), &expected[..]);
+        universe.toggle_cells(&[(9, 9)]);
+        assert!(universe.get_live_cells().contains(&(9.0, 9.0, 1.0)));
+        universe.toggle_obstacle(9, 9, Obstacle::Alive);
+        universe.tick();
+        assert!(!universe.get_live_cells().iter().any(|(row, col, _)| (*row, *col) == (9.0, 9.0)));
+    }
+
+    #[test]
+    fn emitters_toggle_in_still_tiles() {
+        let mut universe = Universe::new(256, 256);
+        universe.set_cells(&[]);
+        universe.toggle_obstacle(200, 200, Obstacle::Emitter { period: 3 });
+        let mut states = Vec::new();
+        for _ in 0..12 {
+            universe.tick();
+            assert!(universe.active_tile_count() <= 1);
+            states.push(universe.get_live_cells().contains(&(200.0, 200.0, 1.0)) as u8);
+        }
+        assert_eq!(states, [1, 1, 0, 0, 0, 1, 1, 1, 0, 0, 0, 1]);
+    }
+
+    #[test]
+    fn regions_run_their_own_rules() {
+        // HighLife's replicator grows into copies of itself, under Life it dies out
+        let replicator = [(10, 12), (10, 13), (10, 14), (11, 11), (11, 14), (12, 10), (12, 14), (13, 10), (13, 13), (14, 10), (14, 11), (14, 12)];
+        let mut life = Universe::new(64, 32);
+        let mut painted = Universe::new(64, 32);
+        let mut highlife = Universe::new(64, 32);
+        highlife.set_rule("B36/S23").unwrap();
+        let shifted: Vec<(usize, usize)> = replicator.iter().map(|(row, col)| (*row, col + 32)).collect();
+        for universe in [&mut life, &mut painted, &mut highlife] {
+            universe.set_cells(&[replicator.to_vec(), shifted.clone()].concat());
+        }
+        let right: Vec<(usize, usize)> = (0..32).flat_map(|row| (32..64).map(move |col| (row, col))).collect();
+        painted.paint_rule(&right, "B36/S23").unwrap();
+        // A border down the middle, the one where the torus wraps around is not drawn
+        assert_eq!(painted.region_edges.len(), 32);
+        for _ in 0..12 {
+            life.tick();
+            painted.tick();
+            highlife.tick();
+        }
+        let half = |universe: &Universe, right: bool| -> Vec<(f32, f32, f32)> {
+            universe.get_live_cells().iter().copied().filter(|(_, col, _)| (*col >= 32.0) == right).collect()
+        };
+        assert_eq!(half(&painted, false), half(&life, false));
+        assert_eq!(half(&painted, true), half(&highlife, true));
+        assert_ne!(half(&life, true), half(&highlife, true));
+        assert!(painted.paint_rule(&right, "B3/S23/C3").is_err());
+        assert!(painted.set_rule("R2,C0,M0,S3..6,B4..5,NM").is_err());
+        painted.clear_regions();
+        assert!(painted.region_edges.is_empty());
+    }
+
+    #[test]
+    fn weighted_rules_sum_their_kernel() {
+        // All ones around the cell is Conway's life
+        let mut life = soup(48, 40);
+        let mut weighted = soup(48, 40);
+        weighted.set_rule("Weighted 1,1,1;1,0,1;1,1,1 B3/S2..3").unwrap();
+        for _ in 0..20 {
+            life.tick();
+            weighted.tick();
+            assert_eq!(weighted.get_live_cells(), life.get_live_cells());
+        }
+        // A lone cell is born east of a live cell only, where the one weight reaches it
+        let mut universe = Universe::new(16, 16);
+        universe.set_rule("Weighted 0,0,0;1,0,0;0,0,0 B1/S").unwrap();
+        universe.set_cells(&[(5, 5)]);
+        universe.tick();
+        assert_eq!(universe.get_live_cells(), &[(5.0, 6.0, 1.0)]);
+    }
+}